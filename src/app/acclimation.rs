@@ -0,0 +1,131 @@
+use super::{
+    config::AcclimationConfig,
+    storage::{read_record, write_record, ACCLIMATION_ADDR},
+    Serial,
+};
+use arduino_hal::Eeprom;
+
+/// Photoperiod growing day after day (new planted tank: less algae). The progress is kept
+/// in EEPROM: a reboot does not restart the program.
+pub struct Acclimation {
+    active: bool,
+    // Days started since the program start (today included)
+    days_done: u16,
+    // Wall day (days since 2000-01-01) of the last counted day, NO_DAY if unknown
+    last_wall_day: u16,
+}
+
+impl Acclimation {
+    const RECORD_LEN: usize = 5;
+    const NO_DAY: u16 = u16::MAX;
+
+    pub fn new(eeprom: &Eeprom) -> Self {
+        let mut record = [0; Self::RECORD_LEN];
+
+        if read_record(eeprom, ACCLIMATION_ADDR, &mut record) {
+            Self {
+                active: record[0] != 0,
+                days_done: u16::from_le_bytes([record[1], record[2]]),
+                last_wall_day: u16::from_le_bytes([record[3], record[4]]),
+            }
+        } else {
+            Self {
+                active: false,
+                days_done: 0,
+                last_wall_day: Self::NO_DAY,
+            }
+        }
+    }
+
+    /// The first day of the program is the next day
+    pub fn start(&mut self, eeprom: &mut Eeprom) {
+        self.active = true;
+        self.days_done = 0;
+        self.last_wall_day = Self::NO_DAY;
+        self.save(eeprom);
+    }
+
+    pub fn stop(&mut self, eeprom: &mut Eeprom) {
+        self.active = false;
+        self.save(eeprom);
+    }
+
+    /// To be called at each day start. Returns the light duration (s) of the day while the
+    /// program runs.
+    /// A day is counted once per wall day (clock set), or at each day start but the restart
+    /// after a watchdog reset (clock not set: a power loss counts an extra day)
+    pub fn new_day(
+        &mut self,
+        wall_day: Option<u32>,
+        restarted: bool,
+        config: &AcclimationConfig,
+        eeprom: &mut Eeprom,
+    ) -> Option<u32> {
+        if !self.active {
+            return None;
+        }
+
+        let is_new_day = match wall_day {
+            Some(wall_day) => wall_day as u16 != self.last_wall_day,
+            None => !restarted || self.days_done == 0,
+        };
+
+        if is_new_day {
+            if self.days_done >= config.days as u16 {
+                // Program done: the configured photoperiod takes over
+                self.active = false;
+                self.save(eeprom);
+
+                return None;
+            }
+
+            self.days_done += 1;
+            self.last_wall_day = wall_day.map(|day| day as u16).unwrap_or(Self::NO_DAY);
+            self.save(eeprom);
+        }
+
+        Some(self.on_duration_s(config))
+    }
+
+    pub fn report(&self, config: &AcclimationConfig, serial: &mut Serial) {
+        if !self.active {
+            ufmt::uwriteln!(serial, "Acclimation: off\r").unwrap();
+        } else if self.days_done == 0 {
+            ufmt::uwriteln!(serial, "Acclimation: starts with the next day\r").unwrap();
+        } else {
+            ufmt::uwriteln!(
+                serial,
+                "Acclimation: day {}/{}, light {} min\r",
+                self.days_done,
+                config.days,
+                self.on_duration_s(config) / 60
+            )
+            .unwrap();
+        }
+    }
+
+    /// Start duration plus a daily increment (0: evenly spread over the days), up to the
+    /// end duration
+    fn on_duration_s(&self, config: &AcclimationConfig) -> u32 {
+        let start_min = config.start_min as u32;
+        let end_min = config.end_min as u32;
+        let day = self.days_done.saturating_sub(1) as u32;
+
+        let increment_min = if config.increment_min > 0 {
+            config.increment_min as u32
+        } else {
+            end_min.saturating_sub(start_min) / (config.days.max(2) as u32 - 1)
+        };
+
+        (start_min + day * increment_min).min(end_min) * 60
+    }
+
+    fn save(&self, eeprom: &mut Eeprom) {
+        let mut record = [0; Self::RECORD_LEN];
+        record[0] = self.active as u8;
+        record[1..3].copy_from_slice(&self.days_done.to_le_bytes());
+        record[3..].copy_from_slice(&self.last_wall_day.to_le_bytes());
+
+        write_record(eeprom, ACCLIMATION_ADDR, &record);
+    }
+}
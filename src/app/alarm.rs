@@ -0,0 +1,328 @@
+use super::{alive::BeatPattern, Serial};
+use crate::drivers::{
+    adc::{Adc, AdcRequest, Channel, Reference},
+    time::timer::Timer,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Warning,
+    Critical,
+}
+
+impl Severity {
+    fn name(&self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Critical => "critical",
+        }
+    }
+}
+
+/// Safety action enforced by the application while the alarm is shown
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum AlarmAction {
+    None,
+    /// No CO2 injection
+    StopCo2,
+    /// No dose
+    StopDosing,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum AlarmId {
+    ClockNotSet = 0,
+    /// Unexpected reset (watchdog, panic)
+    Reset = 1,
+    PhSensor = 2,
+    TopOffTimeout = 3,
+    TopOffHighLevel = 4,
+    DosingEmpty = 5,
+    LampReplace = 6,
+    LampFailure = 7,
+    LightSensor = 8,
+}
+
+struct AlarmSpec {
+    name: &'static str,
+    severity: Severity,
+    /// Stays shown once the condition is gone, until acknowledged
+    latching: bool,
+    pattern: Option<BeatPattern>,
+    action: AlarmAction,
+}
+
+impl AlarmId {
+    const COUNT: usize = 9;
+    const ALL: [AlarmId; Self::COUNT] = [
+        AlarmId::ClockNotSet,
+        AlarmId::Reset,
+        AlarmId::PhSensor,
+        AlarmId::TopOffTimeout,
+        AlarmId::TopOffHighLevel,
+        AlarmId::DosingEmpty,
+        AlarmId::LampReplace,
+        AlarmId::LampFailure,
+        AlarmId::LightSensor,
+    ];
+
+    pub fn from_index(index: u8) -> Option<Self> {
+        Self::ALL.get(index as usize).copied()
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.spec().name
+    }
+
+    fn spec(&self) -> AlarmSpec {
+        match self {
+            AlarmId::ClockNotSet => AlarmSpec {
+                name: "clock not set",
+                severity: Severity::Info,
+                latching: false,
+                pattern: Some(BeatPattern::TimeNotSet),
+                action: AlarmAction::None,
+            },
+            AlarmId::Reset => AlarmSpec {
+                name: "unexpected reset",
+                severity: Severity::Warning,
+                latching: true,
+                pattern: None,
+                action: AlarmAction::None,
+            },
+            // CO2 injected blind could crash the pH
+            AlarmId::PhSensor => AlarmSpec {
+                name: "pH sensor failure",
+                severity: Severity::Warning,
+                latching: true,
+                pattern: Some(BeatPattern::SensorFailure),
+                action: AlarmAction::StopCo2,
+            },
+            AlarmId::TopOffTimeout => AlarmSpec {
+                name: "top-off pump timeout",
+                severity: Severity::Critical,
+                latching: true,
+                pattern: Some(BeatPattern::SensorFailure),
+                action: AlarmAction::None,
+            },
+            AlarmId::TopOffHighLevel => AlarmSpec {
+                name: "top-off high level",
+                severity: Severity::Critical,
+                latching: true,
+                pattern: Some(BeatPattern::SensorFailure),
+                action: AlarmAction::None,
+            },
+            // Running dry damages the pump tubing
+            AlarmId::DosingEmpty => AlarmSpec {
+                name: "dosing container empty",
+                severity: Severity::Warning,
+                latching: false,
+                pattern: None,
+                action: AlarmAction::StopDosing,
+            },
+            // Maintenance reminder: "lamp reset" once replaced
+            AlarmId::LampReplace => AlarmSpec {
+                name: "lamp replacement due",
+                severity: Severity::Info,
+                latching: false,
+                pattern: None,
+                action: AlarmAction::None,
+            },
+            // Light sensor reading far from the commanded level (see LightSensor)
+            AlarmId::LampFailure => AlarmSpec {
+                name: "lamp failure",
+                severity: Severity::Warning,
+                latching: true,
+                pattern: None,
+                action: AlarmAction::None,
+            },
+            // No reading while the lamp check is enabled: BH1750 missing or unplugged
+            AlarmId::LightSensor => AlarmSpec {
+                name: "light sensor failure",
+                severity: Severity::Warning,
+                latching: true,
+                pattern: Some(BeatPattern::SensorFailure),
+                action: AlarmAction::None,
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct AlarmState {
+    active: bool,
+    unacknowledged: bool,
+}
+
+/// Alarms raised and cleared by ID. A shown alarm drives the alive LED pattern, the buzzer
+/// (until acknowledged) and its safety action.
+pub struct AlarmManager {
+    states: [AlarmState; AlarmId::COUNT],
+}
+
+impl AlarmManager {
+    pub fn new() -> Self {
+        Self {
+            states: [AlarmState {
+                active: false,
+                unacknowledged: false,
+            }; AlarmId::COUNT],
+        }
+    }
+
+    /// Returns true when the alarm is newly raised
+    pub fn raise(&mut self, id: AlarmId, serial: &mut Serial) -> bool {
+        let state = &mut self.states[id as usize];
+        if state.active {
+            return false;
+        }
+
+        state.active = true;
+        state.unacknowledged = true;
+
+        let spec = id.spec();
+        ufmt::uwriteln!(serial, "ALARM ({}): {}\r", spec.severity.name(), spec.name).unwrap();
+
+        true
+    }
+
+    pub fn clear(&mut self, id: AlarmId, serial: &mut Serial) {
+        let state = &mut self.states[id as usize];
+        if !state.active {
+            return;
+        }
+
+        state.active = false;
+
+        let spec = id.spec();
+        if spec.latching && state.unacknowledged {
+            ufmt::uwriteln!(serial, "Alarm condition gone (ack needed): {}\r", spec.name).unwrap();
+        } else {
+            state.unacknowledged = false;
+            ufmt::uwriteln!(serial, "Alarm cleared: {}\r", spec.name).unwrap();
+        }
+    }
+
+    /// Raises or clears the alarm. Returns true when the alarm is newly raised
+    pub fn set(&mut self, id: AlarmId, active: bool, serial: &mut Serial) -> bool {
+        if active {
+            self.raise(id, serial)
+        } else {
+            self.clear(id, serial);
+            false
+        }
+    }
+
+    /// Latched alarms whose condition is gone disappear, the others stop sounding
+    pub fn acknowledge(&mut self, serial: &mut Serial) {
+        for state in self.states.iter_mut() {
+            state.unacknowledged = false;
+        }
+
+        ufmt::uwriteln!(serial, "Alarms acknowledged\r").unwrap();
+    }
+
+    pub fn is_pattern_shown(&self, pattern: BeatPattern) -> bool {
+        self.shown().any(|id| id.spec().pattern == Some(pattern))
+    }
+
+    pub fn is_action_requested(&self, action: AlarmAction) -> bool {
+        self.shown().any(|id| id.spec().action == action)
+    }
+
+    /// Highest severity of the unacknowledged alarms, info alarms never sound
+    pub fn sounding_severity(&self) -> Option<Severity> {
+        self.shown()
+            .filter(|id| self.states[*id as usize].unacknowledged)
+            .map(|id| id.spec().severity)
+            .filter(|severity| *severity >= Severity::Warning)
+            .max()
+    }
+
+    pub fn report(&self, serial: &mut Serial) {
+        let mut count = 0;
+
+        for id in self.shown() {
+            let state = &self.states[id as usize];
+            let spec = id.spec();
+
+            ufmt::uwrite!(serial, "{} ({})", spec.name, spec.severity.name()).unwrap();
+            if !state.active {
+                ufmt::uwrite!(serial, ", condition gone").unwrap();
+            }
+            if state.unacknowledged {
+                ufmt::uwrite!(serial, ", not acknowledged").unwrap();
+            }
+            ufmt::uwriteln!(serial, "\r").unwrap();
+
+            count += 1;
+        }
+
+        if count == 0 {
+            ufmt::uwriteln!(serial, "No alarm\r").unwrap();
+        }
+    }
+
+    /// Active, or latched until acknowledged
+    pub fn shown(&self) -> impl Iterator<Item = AlarmId> + '_ {
+        AlarmId::ALL.into_iter().filter(|id| {
+            let state = &self.states[*id as usize];
+            state.active || (id.spec().latching && state.unacknowledged)
+        })
+    }
+}
+
+/// Push button between A7 and GND with an external 10k pull-up: A7 is analog only
+pub struct AckButton {
+    sample_timer: Timer,
+    sampling: bool,
+    // Last raw readings, the press is taken when both agree (debounce)
+    previous_pressed: bool,
+    pressed: bool,
+}
+
+impl AckButton {
+    const SAMPLE_PERIOD_US: u64 = 50_000; // 50ms
+    const ADC_REQUEST: AdcRequest = AdcRequest::new(Channel::Adc7, Reference::Avcc, 0);
+    const PRESSED_MAX_MV: u32 = 1_000;
+
+    pub fn new() -> Self {
+        Self {
+            sample_timer: Timer::new(Self::SAMPLE_PERIOD_US),
+            sampling: false,
+            previous_pressed: false,
+            pressed: false,
+        }
+    }
+
+    pub fn init(&mut self, t_us: u64) {
+        self.sample_timer.start(t_us);
+    }
+
+    /// Returns true when the button is pressed
+    pub fn update(&mut self, t_us: u64, adc: &mut Adc) -> bool {
+        if let Ok(true) = self.sample_timer.has_expired(t_us) {
+            self.sampling = true;
+            self.sample_timer.start(t_us);
+        }
+
+        if self.sampling {
+            if let Some(raw) = adc.read(t_us, &Self::ADC_REQUEST) {
+                self.sampling = false;
+
+                let raw_pressed = Self::ADC_REQUEST.to_millivolts(raw) < Self::PRESSED_MAX_MV;
+                let was_pressed = self.pressed;
+                if raw_pressed == self.previous_pressed {
+                    self.pressed = raw_pressed;
+                }
+                self.previous_pressed = raw_pressed;
+
+                return self.pressed && !was_pressed;
+            }
+        }
+
+        false
+    }
+}
@@ -0,0 +1,109 @@
+use super::task::{Task, TaskContext};
+use crate::drivers::{buzzer::Buzzer, time::timer::Timer};
+
+#[derive(Clone, Copy)]
+pub struct Note {
+    /// 0: silence
+    pub frequency_hz: u16,
+    pub duration_ms: u16,
+}
+
+impl Note {
+    const fn new(frequency_hz: u16, duration_ms: u16) -> Self {
+        Self {
+            frequency_hz,
+            duration_ms,
+        }
+    }
+}
+
+/// Played in a loop
+pub type Melody = &'static [Note];
+
+/// Two-tone siren
+pub const CRITICAL_MELODY: Melody = &[
+    Note::new(2_700, 250),
+    Note::new(2_000, 250),
+    Note::new(2_700, 250),
+    Note::new(2_000, 250),
+    Note::new(0, 1_000),
+];
+
+/// Double beep every 30s
+pub const WARNING_MELODY: Melody = &[
+    Note::new(2_700, 100),
+    Note::new(0, 100),
+    Note::new(2_700, 100),
+    Note::new(0, 30_000),
+];
+
+/// Non-blocking melody player: the notes are sequenced by update()
+pub struct AlertPlayer {
+    buzzer: Buzzer,
+    melody: Option<Melody>,
+    note_index: usize,
+    note_timer: Timer,
+}
+
+impl AlertPlayer {
+    pub fn new(buzzer: Buzzer) -> Self {
+        Self {
+            buzzer,
+            melody: None,
+            note_index: 0,
+            note_timer: Timer::new(0),
+        }
+    }
+
+    /// None stops; the same melody keeps playing where it is
+    pub fn play(&mut self, melody: Option<Melody>, t_us: u64) {
+        let is_same = match (self.melody, melody) {
+            (Some(current), Some(melody)) => core::ptr::eq(current, melody),
+            (None, None) => true,
+            _ => false,
+        };
+        if is_same {
+            return;
+        }
+
+        self.melody = melody;
+
+        if self.melody.is_some() {
+            self.start_note(0, t_us);
+        } else {
+            self.note_timer.stop();
+            self.buzzer.off();
+        }
+    }
+
+    fn start_note(&mut self, note_index: usize, t_us: u64) {
+        if let Some(note) = self.melody.and_then(|melody| melody.get(note_index)) {
+            self.note_index = note_index;
+            self.buzzer.tone(note.frequency_hz);
+
+            self.note_timer = Timer::new((note.duration_ms as u64) * 1_000);
+            self.note_timer.start(t_us);
+        }
+    }
+}
+
+impl Task for AlertPlayer {
+    fn name(&self) -> &'static str {
+        "alert"
+    }
+
+    /// Restarts the current melody
+    fn init(&mut self, context: &mut TaskContext) {
+        if self.melody.is_some() {
+            self.start_note(0, context.t_us);
+        }
+    }
+
+    fn update(&mut self, context: &mut TaskContext) {
+        let t_us = context.t_us;
+
+        if let (Some(melody), Ok(true)) = (self.melody, self.note_timer.has_expired(t_us)) {
+            self.start_note((self.note_index + 1) % melody.len(), t_us);
+        }
+    }
+}
@@ -1,48 +1,143 @@
-use crate::drivers::time::{
-    sys_timer::{ImplTimer, SysTimer},
-    timer::Timer,
+use super::{
+    config::AliveConfig,
+    task::{Task, TaskContext},
 };
+use crate::drivers::time::timer::Timer;
 use arduino_hal::{
     hal::port::PB5,
     port::{mode::Output, Pin},
 };
 
+/// Alive LED patterns, by increasing priority: the highest active one is shown.
+/// The faults are counted in long blinks (e.g. 3 blinks, pause: sensor failure).
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum BeatPattern {
+    /// Configured heartbeat
+    Normal = 0,
+    TimeNotSet = 1,
+    // No food level sensor yet
+    LowFood = 2,
+    SensorFailure = 3,
+    // No home sensor on the feeder yet
+    HomingFailure = 4,
+}
+
+impl BeatPattern {
+    /// By increasing priority
+    pub const FAULTS: [BeatPattern; 4] = [
+        BeatPattern::TimeNotSet,
+        BeatPattern::LowFood,
+        BeatPattern::SensorFailure,
+        BeatPattern::HomingFailure,
+    ];
+}
+
 pub struct AliveBeat {
     led: Pin<Output, PB5>, // Digital pin 13 is also connected to an onboard LED marked "L"
     led_toggle_timer: Timer,
     led_off_timer: Timer,
     led_toggle_count: u32,
+    config: AliveConfig,
+    // Bit per BeatPattern (Normal always set)
+    active_patterns: u8,
+    shown_pattern: BeatPattern,
 }
 
 impl AliveBeat {
-    const LED_TOGGLE_TIMEOUT_US: u64 = 100_000;
-    const LED_OFF_TIMEOUT_US: u64 = 800_000;
-
-    const LED_TOGGLE_MAX_COUNT: u32 = 4;
+    const FAULT_TOGGLE_US: u64 = 400_000; // 400ms
+    const FAULT_OFF_US: u64 = 2_000_000; // 2s
 
     // Digital pin 13 is also connected to an onboard LED marked "L"
-    pub fn new(mut led_pin: Pin<Output, PB5>) -> Self {
+    pub fn new(mut led_pin: Pin<Output, PB5>, config: &AliveConfig) -> Self {
         led_pin.set_low();
 
         Self {
             led: led_pin,
-            led_toggle_timer: Timer::new(Self::LED_TOGGLE_TIMEOUT_US),
-            led_off_timer: Timer::new(Self::LED_OFF_TIMEOUT_US),
+            led_toggle_timer: Timer::new((config.toggle_ms as u64) * 1_000),
+            led_off_timer: Timer::new((config.off_ms as u64) * 1_000),
             led_toggle_count: 0,
+            config: *config,
+            active_patterns: 1 << (BeatPattern::Normal as u8),
+            shown_pattern: BeatPattern::Normal,
+        }
+    }
+
+    /// Applied from the next reset
+    pub fn set_config(&mut self, config: &AliveConfig) {
+        self.config = *config;
+    }
+
+    /// A change of the shown pattern restarts the sequence
+    pub fn set_pattern(&mut self, pattern: BeatPattern, active: bool) {
+        if pattern == BeatPattern::Normal {
+            return;
+        }
+
+        if active {
+            self.active_patterns |= 1 << (pattern as u8);
+        } else {
+            self.active_patterns &= !(1 << (pattern as u8));
         }
     }
 
     pub fn reset(&mut self, t_us: u64) {
-        self.led.set_high();
+        self.shown_pattern = self.highest_pattern();
+
+        let (toggle_us, off_us) = match self.shown_pattern {
+            BeatPattern::Normal => (
+                (self.config.toggle_ms as u64) * 1_000,
+                (self.config.off_ms as u64) * 1_000,
+            ),
+            _ => (Self::FAULT_TOGGLE_US, Self::FAULT_OFF_US),
+        };
+        self.led_toggle_timer = Timer::new(toggle_us);
+        self.led_off_timer = Timer::new(off_us);
+
+        // Fault blinks are light pulses over a dark pause
+        if self.shown_pattern == BeatPattern::Normal {
+            self.led.set_high();
+        } else {
+            self.led.set_low();
+        }
 
         self.led_toggle_count = 0;
         self.led_off_timer.start(t_us);
     }
 
-    pub fn update<WhichTimer: ImplTimer>(&mut self, sys_timer: &mut SysTimer<WhichTimer>) {
-        let t = sys_timer.micros();
+    fn highest_pattern(&self) -> BeatPattern {
+        BeatPattern::FAULTS
+            .into_iter()
+            .rev()
+            .find(|pattern| self.active_patterns & (1 << (*pattern as u8)) != 0)
+            .unwrap_or(BeatPattern::Normal)
+    }
+
+    fn toggle_count(&self) -> u32 {
+        match self.shown_pattern {
+            BeatPattern::Normal => self.config.toggle_count as u32,
+            // One blink = 2 toggles
+            pattern => 2 * (pattern as u32),
+        }
+    }
+}
+
+impl Task for AliveBeat {
+    fn name(&self) -> &'static str {
+        "alive"
+    }
+
+    fn init(&mut self, context: &mut TaskContext) {
+        self.reset(context.t_us);
+    }
+
+    fn update(&mut self, context: &mut TaskContext) {
+        let t = context.t_us;
+
+        if self.highest_pattern() != self.shown_pattern {
+            self.reset(t);
+        }
 
-        if self.led_toggle_count == Self::LED_TOGGLE_MAX_COUNT {
+        if self.led_toggle_count == self.toggle_count() {
             self.led_toggle_count = 0;
             self.led_toggle_timer.stop();
             self.led_off_timer.start(t);
@@ -0,0 +1,226 @@
+use super::Serial;
+use core::fmt::Debug;
+use ufmt::uWrite;
+
+/// Civil date and time
+#[derive(Clone, Copy)]
+pub struct DateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl DateTime {
+    /// Seconds since 2000-01-01 00:00:00
+    pub fn from_epoch_s(epoch_s: u32) -> Self {
+        let days = epoch_s / 86_400;
+        let second_of_day = epoch_s % 86_400;
+
+        let (year, month, day) = civil_from_days(days);
+
+        Self {
+            year,
+            month,
+            day,
+            hour: (second_of_day / 3_600) as u8,
+            minute: (second_of_day % 3_600 / 60) as u8,
+            second: (second_of_day % 60) as u8,
+        }
+    }
+
+    /// None if the date is invalid or out of the u32 range (2136)
+    pub fn to_epoch_s(&self) -> Option<u32> {
+        if self.year < 2000
+            || !(1..=12).contains(&self.month)
+            || !(1..=days_in_month(self.year, self.month)).contains(&self.day)
+            || self.hour > 23
+            || self.minute > 59
+            || self.second > 59
+        {
+            return None;
+        }
+
+        let days = days_from_civil(self.year, self.month, self.day);
+        let second_of_day =
+            (self.hour as u32) * 3_600 + (self.minute as u32) * 60 + self.second as u32;
+
+        days.checked_mul(86_400)?.checked_add(second_of_day)
+    }
+
+    /// "YYYY-MM-DD HH:MM:SS"
+    pub fn parse(text: &[u8]) -> Option<Self> {
+        if text.len() != 19
+            || text[4] != b'-'
+            || text[7] != b'-'
+            || text[10] != b' '
+            || text[13] != b':'
+            || text[16] != b':'
+        {
+            return None;
+        }
+
+        let number = |range: core::ops::Range<usize>| -> Option<u16> {
+            text[range].iter().try_fold(0u16, |value, digit| {
+                digit
+                    .is_ascii_digit()
+                    .then(|| value * 10 + (digit - b'0') as u16)
+            })
+        };
+
+        let date_time = Self {
+            year: number(0..4)?,
+            month: number(5..7)? as u8,
+            day: number(8..10)? as u8,
+            hour: number(11..13)? as u8,
+            minute: number(14..16)? as u8,
+            second: number(17..19)? as u8,
+        };
+
+        // Range check
+        date_time.to_epoch_s().map(|_| date_time)
+    }
+
+    pub fn write(&self, serial: &mut Serial) {
+        ufmt::uwrite!(serial, "{}-", self.year).unwrap();
+        write_2_digits(serial, self.month);
+        ufmt::uwrite!(serial, "-").unwrap();
+        write_2_digits(serial, self.day);
+        ufmt::uwrite!(serial, " ").unwrap();
+        write_2_digits(serial, self.hour);
+        ufmt::uwrite!(serial, ":").unwrap();
+        write_2_digits(serial, self.minute);
+        ufmt::uwrite!(serial, ":").unwrap();
+        write_2_digits(serial, self.second);
+    }
+}
+
+fn write_2_digits<W: uWrite>(writer: &mut W, value: u8)
+where
+    W::Error: Debug,
+{
+    if value < 10 {
+        ufmt::uwrite!(writer, "0").unwrap();
+    }
+    ufmt::uwrite!(writer, "{}", value).unwrap();
+}
+
+/// "HH:MM", from the seconds since midnight (serial, display line)
+pub fn write_time_of_day<W: uWrite>(writer: &mut W, second_of_day: u32)
+where
+    W::Error: Debug,
+{
+    let second_of_day = second_of_day % 86_400;
+
+    write_2_digits(writer, (second_of_day / 3_600) as u8);
+    ufmt::uwrite!(writer, ":").unwrap();
+    write_2_digits(writer, (second_of_day % 3_600 / 60) as u8);
+}
+
+fn is_leap_year(year: u16) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 2000-01-01 (proleptic Gregorian calendar)
+pub fn days_from_civil(year: u16, month: u8, day: u8) -> u32 {
+    // March based year: the leap day is the last day of the year
+    let (year, month) = if month <= 2 {
+        (year as u32 - 1, month as u32 + 9)
+    } else {
+        (year as u32, month as u32 - 3)
+    };
+
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * month + 2) / 5 + day as u32 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    // 730_425: days from 0000-03-01 to 2000-01-01
+    era * 146_097 + day_of_era - 730_425
+}
+
+/// 0: Monday .. 6: Sunday, from the days since 2000-01-01 (a Saturday)
+pub fn weekday(days: u32) -> u8 {
+    ((days + 5) % 7) as u8
+}
+
+pub fn civil_from_days(days: u32) -> (u16, u8, u8) {
+    let days = days + 730_425;
+
+    let era = days / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+
+    let (year, month) = if month < 10 {
+        (year_of_era + era * 400, month + 3)
+    } else {
+        (year_of_era + era * 400 + 1, month - 9)
+    };
+
+    (year as u16, month as u8, day as u8)
+}
+
+/// Wall clock running on the system timer.
+/// Until it is set over the serial interface, it counts from the boot.
+pub struct Clock {
+    // Wall time (us since 2000-01-01) when the system timer was at 0
+    base_us: u64,
+    is_set: bool,
+}
+
+impl Clock {
+    pub fn new() -> Self {
+        Self {
+            base_us: 0,
+            is_set: false,
+        }
+    }
+
+    pub fn set(&mut self, t_us: u64, epoch_s: u32) {
+        self.base_us = (epoch_s as u64) * 1_000_000 - t_us;
+        self.is_set = true;
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.is_set
+    }
+
+    /// To be called with the time at which the system timer is reset to 0
+    pub fn on_time_reset(&mut self, t_reset_us: u64) {
+        self.base_us += t_reset_us;
+    }
+
+    /// Seconds since 2000-01-01 if the clock is set, since the boot otherwise
+    pub fn now_s(&self, t_us: u64) -> u32 {
+        ((self.base_us + t_us) / 1_000_000) as u32
+    }
+
+    pub fn report(&self, t_us: u64, serial: &mut Serial) {
+        if self.is_set {
+            DateTime::from_epoch_s(self.now_s(t_us)).write(serial);
+            ufmt::uwriteln!(serial, "\r").unwrap();
+        } else {
+            ufmt::uwriteln!(
+                serial,
+                "Time not set ({}s since boot), send 'time YYYY-MM-DD HH:MM:SS'\r",
+                self.now_s(t_us)
+            )
+            .unwrap();
+        }
+    }
+}
@@ -0,0 +1,953 @@
+use super::{
+    storage::{read_record, write_record, CONFIG_ADDR},
+    wavemaker::{WavePattern, WaveSettings},
+    Serial,
+};
+use arduino_hal::Eeprom;
+
+#[derive(Clone, Copy)]
+pub struct LightConfig {
+    pub on_duration_s: u32,
+    /// On from sunrise to sunset instead of on_duration_s from the day start
+    pub follow_sun: bool,
+    pub latitude_cdeg: i16,  // (0.01 deg, north positive)
+    pub longitude_cdeg: i16, // (0.01 deg, east positive)
+    /// Local time (the clock) - UTC
+    pub utc_offset_min: i16,
+    /// Day length clamp when following the sun
+    pub min_on_min: u16,
+    pub max_on_min: u16,
+    /// Night light intensity at full moon, 0: no moonlight
+    pub moon_max_pct: u8,
+    /// Replacement reminder (full power hours), 0: none
+    pub lamp_life_h: u16,
+    /// Light sensor reading at full power, 0: no lamp check (see LightSensor)
+    pub full_lux: u16,
+    /// Output trimmed to read full_lux x level instead of only checking
+    pub closed_loop: bool,
+}
+
+/// Effects over the daylight (see Weather)
+#[derive(Clone, Copy)]
+pub struct WeatherConfig {
+    /// Cloud dimming, 0: no clouds
+    pub cloud_depth_pct: u8,
+    /// On average
+    pub clouds_per_h: u8,
+    pub cloud_max_s: u16,
+    /// Chance of a storm per day
+    pub storm_pct: u8,
+    pub storm_max_min: u8,
+    /// Weekdays with effects, bit 0: Monday .. bit 6: Sunday
+    pub days: u8,
+    pub seed: u16,
+}
+
+/// Photoperiod ramp of a new planted tank (see Acclimation)
+#[derive(Clone, Copy)]
+pub struct AcclimationConfig {
+    pub start_min: u16,
+    pub end_min: u16,
+    pub days: u8,
+    /// Per day, 0: from start_min to end_min over the days
+    pub increment_min: u8,
+}
+
+/// Status display (see StatusDisplay)
+#[derive(Clone, Copy)]
+pub struct DisplayConfig {
+    /// Summary and alarm pages alternation while alarms are shown, 0: summary only
+    pub page_s: u8,
+    /// Off while the light is off (OLED burn-in), unless an alarm is shown
+    pub night_sleep: bool,
+}
+
+#[derive(Clone, Copy)]
+pub struct FeederConfig {
+    pub delivery_angle_ddeg: u16, // (0.1 deg)
+    pub delivery_speed_deg_s: u16,
+    pub vibration_ampl_ddeg: u16, // (0.1 deg)
+    pub vibration_speed_deg_s: u16,
+    pub vibration_count: u8,
+}
+
+impl FeederConfig {
+    // A portion is a fraction of a turn
+    const MAX_ANGLE_DDEG: u16 = 600; // 60 deg
+    const MAX_VIBRATION_AMPL_DDEG: u16 = 300; // 30 deg
+
+    // 28BYJ-48: a few rpm keeps the torque, ~15 rpm at most
+    const MIN_SPEED_DEG_S: u16 = 15;
+    const MAX_SPEED_DEG_S: u16 = 60;
+
+    /// Longest blocking move allowed by the ranges (ms): a delivery, or a vibration there
+    /// and back, at the slowest speed
+    pub const MAX_MOVE_MS: u32 = {
+        let widest_ddeg = if Self::MAX_ANGLE_DDEG > 2 * Self::MAX_VIBRATION_AMPL_DDEG {
+            Self::MAX_ANGLE_DDEG
+        } else {
+            2 * Self::MAX_VIBRATION_AMPL_DDEG
+        };
+
+        (widest_ddeg as u32) * 100 / (Self::MIN_SPEED_DEG_S as u32)
+    };
+
+    /// Within the ranges accepted by Config::set
+    fn is_valid(&self) -> bool {
+        let speed_range = Self::MIN_SPEED_DEG_S..=Self::MAX_SPEED_DEG_S;
+
+        (1..=Self::MAX_ANGLE_DDEG).contains(&self.delivery_angle_ddeg)
+            && speed_range.contains(&self.delivery_speed_deg_s)
+            && (1..=Self::MAX_VIBRATION_AMPL_DDEG).contains(&self.vibration_ampl_ddeg)
+            && speed_range.contains(&self.vibration_speed_deg_s)
+            && self.vibration_count > 0
+    }
+}
+
+/// Daily fertilizer program (see DosingPump)
+#[derive(Clone, Copy)]
+pub struct DosingConfig {
+    /// 0: no daily program
+    pub daily_ml: u8,
+    /// The daily volume is split into small doses, evenly spread over the day
+    pub doses_per_day: u8,
+}
+
+impl DosingConfig {
+    /// Safety limit: whatever happens (schedule, calibration error), no more is dosed per day
+    pub const MAX_DAILY_ML: u8 = 12;
+    const MAX_DOSES_PER_DAY: u8 = 24;
+}
+
+/// Filter pump pause around the feedings (see Filter)
+#[derive(Clone, Copy)]
+pub struct FilterConfig {
+    pub pause_before_s: u16,
+    pub pause_after_s: u16,
+}
+
+impl FilterConfig {
+    /// Hard limit: the filter is never left off longer, whatever happens to the feeding
+    pub const MAX_PAUSE_S: u16 = 10 * 60; // 10min
+}
+
+/// Circulation pumps (see Wavemaker)
+#[derive(Clone, Copy)]
+pub struct WaveConfig {
+    pub day: WaveSettings,
+    /// While the light is off
+    pub night: WaveSettings,
+}
+
+impl WaveConfig {
+    const MIN_PERIOD_S: u16 = 2;
+    const MAX_PERIOD_S: u16 = 60 * 60; // 1h
+}
+
+#[derive(Clone, Copy)]
+pub struct AliveConfig {
+    pub toggle_ms: u16,
+    pub off_ms: u16,
+    pub toggle_count: u8,
+}
+
+#[derive(Clone, Copy)]
+pub struct BuzzerConfig {
+    /// Only the critical alarms sound
+    pub muted: bool,
+    /// Quiet hours (wall clock, start == end: none): only the critical alarms sound
+    pub quiet_start_h: u8,
+    pub quiet_end_h: u8,
+}
+
+impl BuzzerConfig {
+    /// Unknown time (clock not set): not quiet
+    pub fn is_quiet(&self, hour: Option<u8>) -> bool {
+        match hour {
+            Some(_) if self.quiet_start_h == self.quiet_end_h => false,
+            Some(hour) if self.quiet_start_h < self.quiet_end_h => {
+                (self.quiet_start_h..self.quiet_end_h).contains(&hour)
+            }
+            // Across midnight
+            Some(hour) => hour >= self.quiet_start_h || hour < self.quiet_end_h,
+            None => false,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Config {
+    pub day_s: u32,
+    pub light: LightConfig,
+    pub feeder: FeederConfig,
+    pub alive: AliveConfig,
+    pub buzzer: BuzzerConfig,
+    pub weather: WeatherConfig,
+    pub acclimation: AcclimationConfig,
+    pub display: DisplayConfig,
+    pub dosing: DosingConfig,
+    pub filter: FilterConfig,
+    pub wave: WaveConfig,
+}
+
+pub enum ConfigStatus {
+    Loaded,
+    /// Written by a firmware with an older (shorter) layout: missing fields set to their defaults
+    Migrated,
+    /// Never written, corrupted or incompatible version
+    Defaults,
+}
+
+#[derive(Clone, Copy)]
+pub enum ConfigKey {
+    DayS,
+    LightOnS,
+    FeedAngleDdeg,
+    FeedSpeedDegS,
+    VibrationAmplDdeg,
+    VibrationSpeedDegS,
+    VibrationCount,
+    AliveToggleMs,
+    AliveOffMs,
+    AliveToggleCount,
+    BuzzerMuted,
+    QuietStartH,
+    QuietEndH,
+    LightSun,
+    SunLatCdeg,
+    SunLonCdeg,
+    UtcOffsetMin,
+    LightMinMin,
+    LightMaxMin,
+    MoonMaxPct,
+    CloudDepthPct,
+    CloudsPerH,
+    CloudMaxS,
+    StormPct,
+    StormMaxMin,
+    WeatherDays,
+    WeatherSeed,
+    AcclimStartMin,
+    AcclimEndMin,
+    AcclimDays,
+    AcclimStepMin,
+    LampLifeH,
+    LampLux,
+    LightLoop,
+    DisplayPageS,
+    DisplaySleep,
+    DoseDailyMl,
+    DosesPerDay,
+    FilterBeforeS,
+    FilterAfterS,
+    WaveDayPattern,
+    WaveDayMinPct,
+    WaveDayMaxPct,
+    WaveDayPeriodS,
+    WaveNightPattern,
+    WaveNightMinPct,
+    WaveNightMaxPct,
+    WaveNightPeriodS,
+}
+
+impl ConfigKey {
+    const ALL: [ConfigKey; 48] = [
+        ConfigKey::DayS,
+        ConfigKey::LightOnS,
+        ConfigKey::FeedAngleDdeg,
+        ConfigKey::FeedSpeedDegS,
+        ConfigKey::VibrationAmplDdeg,
+        ConfigKey::VibrationSpeedDegS,
+        ConfigKey::VibrationCount,
+        ConfigKey::AliveToggleMs,
+        ConfigKey::AliveOffMs,
+        ConfigKey::AliveToggleCount,
+        ConfigKey::BuzzerMuted,
+        ConfigKey::QuietStartH,
+        ConfigKey::QuietEndH,
+        ConfigKey::LightSun,
+        ConfigKey::SunLatCdeg,
+        ConfigKey::SunLonCdeg,
+        ConfigKey::UtcOffsetMin,
+        ConfigKey::LightMinMin,
+        ConfigKey::LightMaxMin,
+        ConfigKey::MoonMaxPct,
+        ConfigKey::CloudDepthPct,
+        ConfigKey::CloudsPerH,
+        ConfigKey::CloudMaxS,
+        ConfigKey::StormPct,
+        ConfigKey::StormMaxMin,
+        ConfigKey::WeatherDays,
+        ConfigKey::WeatherSeed,
+        ConfigKey::AcclimStartMin,
+        ConfigKey::AcclimEndMin,
+        ConfigKey::AcclimDays,
+        ConfigKey::AcclimStepMin,
+        ConfigKey::LampLifeH,
+        ConfigKey::LampLux,
+        ConfigKey::LightLoop,
+        ConfigKey::DisplayPageS,
+        ConfigKey::DisplaySleep,
+        ConfigKey::DoseDailyMl,
+        ConfigKey::DosesPerDay,
+        ConfigKey::FilterBeforeS,
+        ConfigKey::FilterAfterS,
+        ConfigKey::WaveDayPattern,
+        ConfigKey::WaveDayMinPct,
+        ConfigKey::WaveDayMaxPct,
+        ConfigKey::WaveDayPeriodS,
+        ConfigKey::WaveNightPattern,
+        ConfigKey::WaveNightMinPct,
+        ConfigKey::WaveNightMaxPct,
+        ConfigKey::WaveNightPeriodS,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            ConfigKey::DayS => "day_s",
+            ConfigKey::LightOnS => "light_on_s",
+            ConfigKey::FeedAngleDdeg => "feed_angle_ddeg",
+            ConfigKey::FeedSpeedDegS => "feed_speed_deg_s",
+            ConfigKey::VibrationAmplDdeg => "vib_ampl_ddeg",
+            ConfigKey::VibrationSpeedDegS => "vib_speed_deg_s",
+            ConfigKey::VibrationCount => "vib_count",
+            ConfigKey::AliveToggleMs => "alive_toggle_ms",
+            ConfigKey::AliveOffMs => "alive_off_ms",
+            ConfigKey::AliveToggleCount => "alive_toggle_count",
+            ConfigKey::BuzzerMuted => "buzzer_muted",
+            ConfigKey::QuietStartH => "quiet_start_h",
+            ConfigKey::QuietEndH => "quiet_end_h",
+            ConfigKey::LightSun => "light_sun",
+            ConfigKey::SunLatCdeg => "sun_lat_cdeg",
+            ConfigKey::SunLonCdeg => "sun_lon_cdeg",
+            ConfigKey::UtcOffsetMin => "utc_offset_min",
+            ConfigKey::LightMinMin => "light_min_min",
+            ConfigKey::LightMaxMin => "light_max_min",
+            ConfigKey::MoonMaxPct => "moon_max_pct",
+            ConfigKey::CloudDepthPct => "cloud_depth_pct",
+            ConfigKey::CloudsPerH => "clouds_per_h",
+            ConfigKey::CloudMaxS => "cloud_max_s",
+            ConfigKey::StormPct => "storm_pct",
+            ConfigKey::StormMaxMin => "storm_max_min",
+            ConfigKey::WeatherDays => "weather_days",
+            ConfigKey::WeatherSeed => "weather_seed",
+            ConfigKey::AcclimStartMin => "acclim_start_min",
+            ConfigKey::AcclimEndMin => "acclim_end_min",
+            ConfigKey::AcclimDays => "acclim_days",
+            ConfigKey::AcclimStepMin => "acclim_step_min",
+            ConfigKey::LampLifeH => "lamp_life_h",
+            ConfigKey::LampLux => "lamp_lux",
+            ConfigKey::LightLoop => "light_loop",
+            ConfigKey::DisplayPageS => "display_page_s",
+            ConfigKey::DisplaySleep => "display_sleep",
+            ConfigKey::DoseDailyMl => "dose_daily_ml",
+            ConfigKey::DosesPerDay => "doses_per_day",
+            ConfigKey::FilterBeforeS => "filter_before_s",
+            ConfigKey::FilterAfterS => "filter_after_s",
+            ConfigKey::WaveDayPattern => "wave_day_pattern",
+            ConfigKey::WaveDayMinPct => "wave_day_min_pct",
+            ConfigKey::WaveDayMaxPct => "wave_day_max_pct",
+            ConfigKey::WaveDayPeriodS => "wave_day_period_s",
+            ConfigKey::WaveNightPattern => "wave_night_pattern",
+            ConfigKey::WaveNightMinPct => "wave_night_min_pct",
+            ConfigKey::WaveNightMaxPct => "wave_night_max_pct",
+            ConfigKey::WaveNightPeriodS => "wave_night_period_s",
+        }
+    }
+
+    pub fn from_name(name: &[u8]) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .find(|key| key.name().as_bytes() == name)
+            .copied()
+    }
+}
+
+/// Little endian field reader: fields missing from an older layout keep their default value
+struct FieldReader<'a> {
+    bytes: &'a [u8],
+    index: usize,
+}
+
+impl<'a> FieldReader<'a> {
+    fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let field = self.bytes.get(self.index..self.index + N)?;
+        self.index += N;
+
+        let mut bytes = [0; N];
+        bytes.copy_from_slice(field);
+
+        Some(bytes)
+    }
+
+    fn u8(&mut self, default: u8) -> u8 {
+        self.take::<1>().map(|bytes| bytes[0]).unwrap_or(default)
+    }
+
+    fn u16(&mut self, default: u16) -> u16 {
+        self.take::<2>().map(u16::from_le_bytes).unwrap_or(default)
+    }
+
+    fn i16(&mut self, default: i16) -> i16 {
+        self.take::<2>().map(i16::from_le_bytes).unwrap_or(default)
+    }
+
+    fn u32(&mut self, default: u32) -> u32 {
+        self.take::<4>().map(u32::from_le_bytes).unwrap_or(default)
+    }
+
+    fn wave_settings(&mut self, default: WaveSettings) -> WaveSettings {
+        WaveSettings {
+            pattern: WavePattern::from_code(self.u8(default.pattern.code()))
+                .unwrap_or(default.pattern),
+            min_power_pct: self.u8(default.min_power_pct),
+            max_power_pct: self.u8(default.max_power_pct),
+            period_s: self.u16(default.period_s),
+        }
+    }
+}
+
+struct FieldWriter<'a> {
+    bytes: &'a mut [u8],
+    index: usize,
+}
+
+impl<'a> FieldWriter<'a> {
+    fn put(&mut self, field: &[u8]) {
+        self.bytes[self.index..self.index + field.len()].copy_from_slice(field);
+        self.index += field.len();
+    }
+
+    fn wave_settings(&mut self, settings: &WaveSettings) {
+        self.put(&[
+            settings.pattern.code(),
+            settings.min_power_pct,
+            settings.max_power_pct,
+        ]);
+        self.put(&settings.period_s.to_le_bytes());
+    }
+}
+
+/// EEPROM record: [version, payload length, payload.., CRC8].
+/// The payload layout is append only: new fields go at the end, so an older layout
+/// can be migrated. Any other layout change bumps VERSION (=> defaults).
+impl Config {
+    const VERSION: u8 = 1;
+    const HEADER_LEN: usize = 2;
+    const MIN_DAY_S: u32 = 60 * 60; // 1h
+    const PAYLOAD_LEN: usize = 4 + 4 // day, light on
+        + 2 * 4 + 1 // feeder
+        + 2 * 2 + 1 // alive
+        + 3 // buzzer
+        + 1 + 2 * 5 + 1 // sun, moon
+        + 2 + 2 + 3 + 2 // weather
+        + 2 * 2 + 2 // acclimation
+        + 2 + 2 + 1 // lamp
+        + 2 // display
+        + 2 // dosing
+        + 2 * 2 // filter
+        + 2 * (3 + 2); // wave
+
+    pub const fn default() -> Self {
+        Self {
+            day_s: 24 * 60 * 60, // 24h
+            light: LightConfig {
+                on_duration_s: 7 * 60 * 60, // 7h
+                follow_sun: false,
+                // Paris
+                latitude_cdeg: 4_885,
+                longitude_cdeg: 235,
+                utc_offset_min: 60,
+                min_on_min: 6 * 60,  // 6h
+                max_on_min: 10 * 60, // 10h
+                moon_max_pct: 0,
+                lamp_life_h: 20_000,
+                full_lux: 0,
+                closed_loop: false,
+            },
+            feeder: FeederConfig {
+                delivery_angle_ddeg: 225, // 360/16 = 22.5
+                delivery_speed_deg_s: 35,
+                vibration_ampl_ddeg: 130,
+                vibration_speed_deg_s: 35,
+                vibration_count: 10,
+            },
+            alive: AliveConfig {
+                toggle_ms: 100,
+                off_ms: 800,
+                toggle_count: 4,
+            },
+            buzzer: BuzzerConfig {
+                muted: false,
+                quiet_start_h: 22,
+                quiet_end_h: 8,
+            },
+            weather: WeatherConfig {
+                cloud_depth_pct: 0,
+                clouds_per_h: 4,
+                cloud_max_s: 120, // 2min
+                storm_pct: 0,
+                storm_max_min: 15,
+                days: 0b111_1111,
+                seed: 1,
+            },
+            acclimation: AcclimationConfig {
+                start_min: 4 * 60, // 4h
+                end_min: 8 * 60,   // 8h
+                days: 42,          // 6 weeks
+                increment_min: 0,
+            },
+            display: DisplayConfig {
+                page_s: 5,
+                night_sleep: true,
+            },
+            dosing: DosingConfig {
+                daily_ml: 8,
+                doses_per_day: 4,
+            },
+            filter: FilterConfig {
+                pause_before_s: 30,    // 30s
+                pause_after_s: 5 * 60, // 5min
+            },
+            wave: WaveConfig {
+                day: WaveSettings {
+                    pattern: WavePattern::Alternating,
+                    min_power_pct: 30,
+                    max_power_pct: 90,
+                    period_s: 20,
+                },
+                night: WaveSettings {
+                    pattern: WavePattern::Night,
+                    min_power_pct: 20,
+                    max_power_pct: 40,
+                    period_s: 2 * 60, // 2min
+                },
+            },
+        }
+    }
+
+    pub fn load(eeprom: &mut Eeprom) -> (Self, ConfigStatus) {
+        let version = eeprom.read_byte(CONFIG_ADDR);
+        let payload_len = eeprom.read_byte(CONFIG_ADDR + 1) as usize;
+
+        if version != Self::VERSION || payload_len > Self::PAYLOAD_LEN {
+            return (Self::default(), ConfigStatus::Defaults);
+        }
+
+        let mut record = [0; Self::HEADER_LEN + Self::PAYLOAD_LEN];
+        let record = &mut record[..Self::HEADER_LEN + payload_len];
+        if !read_record(eeprom, CONFIG_ADDR, record) {
+            return (Self::default(), ConfigStatus::Defaults);
+        }
+
+        let config = Self::decode(&record[Self::HEADER_LEN..]);
+
+        if payload_len < Self::PAYLOAD_LEN {
+            // Rewritten with the current layout
+            config.save(eeprom);
+
+            (config, ConfigStatus::Migrated)
+        } else {
+            (config, ConfigStatus::Loaded)
+        }
+    }
+
+    /// Only the changed bytes are written (see storage::write_record)
+    pub fn save(&self, eeprom: &mut Eeprom) {
+        let mut record = [0; Self::HEADER_LEN + Self::PAYLOAD_LEN];
+        record[0] = Self::VERSION;
+        record[1] = Self::PAYLOAD_LEN as u8;
+
+        self.encode(&mut record[Self::HEADER_LEN..]);
+
+        write_record(eeprom, CONFIG_ADDR, &record);
+    }
+
+    fn decode(payload: &[u8]) -> Self {
+        let default = Self::default();
+        let mut reader = FieldReader {
+            bytes: payload,
+            index: 0,
+        };
+
+        // Same order as encode()
+        let mut config = Self {
+            day_s: reader.u32(default.day_s),
+            light: LightConfig {
+                on_duration_s: reader.u32(default.light.on_duration_s),
+                ..default.light
+            },
+            feeder: FeederConfig {
+                delivery_angle_ddeg: reader.u16(default.feeder.delivery_angle_ddeg),
+                delivery_speed_deg_s: reader.u16(default.feeder.delivery_speed_deg_s),
+                vibration_ampl_ddeg: reader.u16(default.feeder.vibration_ampl_ddeg),
+                vibration_speed_deg_s: reader.u16(default.feeder.vibration_speed_deg_s),
+                vibration_count: reader.u8(default.feeder.vibration_count),
+            },
+            alive: AliveConfig {
+                toggle_ms: reader.u16(default.alive.toggle_ms),
+                off_ms: reader.u16(default.alive.off_ms),
+                toggle_count: reader.u8(default.alive.toggle_count),
+            },
+            buzzer: BuzzerConfig {
+                muted: reader.u8(default.buzzer.muted as u8) != 0,
+                quiet_start_h: reader.u8(default.buzzer.quiet_start_h),
+                quiet_end_h: reader.u8(default.buzzer.quiet_end_h),
+            },
+            weather: default.weather,
+            acclimation: default.acclimation,
+            display: default.display,
+            dosing: default.dosing,
+            filter: default.filter,
+            wave: default.wave,
+        };
+
+        // Appended fields
+        config.light.follow_sun = reader.u8(default.light.follow_sun as u8) != 0;
+        config.light.latitude_cdeg = reader.i16(default.light.latitude_cdeg);
+        config.light.longitude_cdeg = reader.i16(default.light.longitude_cdeg);
+        config.light.utc_offset_min = reader.i16(default.light.utc_offset_min);
+        config.light.min_on_min = reader.u16(default.light.min_on_min);
+        config.light.max_on_min = reader.u16(default.light.max_on_min);
+        config.light.moon_max_pct = reader.u8(default.light.moon_max_pct);
+        config.weather = WeatherConfig {
+            cloud_depth_pct: reader.u8(default.weather.cloud_depth_pct),
+            clouds_per_h: reader.u8(default.weather.clouds_per_h),
+            cloud_max_s: reader.u16(default.weather.cloud_max_s),
+            storm_pct: reader.u8(default.weather.storm_pct),
+            storm_max_min: reader.u8(default.weather.storm_max_min),
+            days: reader.u8(default.weather.days),
+            seed: reader.u16(default.weather.seed),
+        };
+        config.acclimation = AcclimationConfig {
+            start_min: reader.u16(default.acclimation.start_min),
+            end_min: reader.u16(default.acclimation.end_min),
+            days: reader.u8(default.acclimation.days),
+            increment_min: reader.u8(default.acclimation.increment_min),
+        };
+        config.light.lamp_life_h = reader.u16(default.light.lamp_life_h);
+        config.light.full_lux = reader.u16(default.light.full_lux);
+        config.light.closed_loop = reader.u8(default.light.closed_loop as u8) != 0;
+        config.display = DisplayConfig {
+            page_s: reader.u8(default.display.page_s),
+            night_sleep: reader.u8(default.display.night_sleep as u8) != 0,
+        };
+        config.dosing = DosingConfig {
+            daily_ml: reader.u8(default.dosing.daily_ml),
+            doses_per_day: reader.u8(default.dosing.doses_per_day),
+        };
+        config.filter = FilterConfig {
+            pause_before_s: reader.u16(default.filter.pause_before_s),
+            pause_after_s: reader.u16(default.filter.pause_after_s),
+        };
+        config.wave = WaveConfig {
+            day: reader.wave_settings(default.wave.day),
+            night: reader.wave_settings(default.wave.night),
+        };
+
+        // Stored before the day length was checked
+        if config.day_s < Self::MIN_DAY_S || config.light.on_duration_s > config.day_s {
+            config.day_s = default.day_s;
+            config.light.on_duration_s = default.light.on_duration_s;
+        }
+        // Stored before the feeder ranges were checked
+        if !config.feeder.is_valid() {
+            config.feeder = default.feeder;
+        }
+
+        config
+    }
+
+    fn encode(&self, payload: &mut [u8]) {
+        let mut writer = FieldWriter {
+            bytes: payload,
+            index: 0,
+        };
+
+        writer.put(&self.day_s.to_le_bytes());
+        writer.put(&self.light.on_duration_s.to_le_bytes());
+        writer.put(&self.feeder.delivery_angle_ddeg.to_le_bytes());
+        writer.put(&self.feeder.delivery_speed_deg_s.to_le_bytes());
+        writer.put(&self.feeder.vibration_ampl_ddeg.to_le_bytes());
+        writer.put(&self.feeder.vibration_speed_deg_s.to_le_bytes());
+        writer.put(&[self.feeder.vibration_count]);
+        writer.put(&self.alive.toggle_ms.to_le_bytes());
+        writer.put(&self.alive.off_ms.to_le_bytes());
+        writer.put(&[self.alive.toggle_count]);
+        writer.put(&[
+            self.buzzer.muted as u8,
+            self.buzzer.quiet_start_h,
+            self.buzzer.quiet_end_h,
+        ]);
+        writer.put(&[self.light.follow_sun as u8]);
+        writer.put(&self.light.latitude_cdeg.to_le_bytes());
+        writer.put(&self.light.longitude_cdeg.to_le_bytes());
+        writer.put(&self.light.utc_offset_min.to_le_bytes());
+        writer.put(&self.light.min_on_min.to_le_bytes());
+        writer.put(&self.light.max_on_min.to_le_bytes());
+        writer.put(&[self.light.moon_max_pct]);
+        writer.put(&[self.weather.cloud_depth_pct, self.weather.clouds_per_h]);
+        writer.put(&self.weather.cloud_max_s.to_le_bytes());
+        writer.put(&[
+            self.weather.storm_pct,
+            self.weather.storm_max_min,
+            self.weather.days,
+        ]);
+        writer.put(&self.weather.seed.to_le_bytes());
+        writer.put(&self.acclimation.start_min.to_le_bytes());
+        writer.put(&self.acclimation.end_min.to_le_bytes());
+        writer.put(&[self.acclimation.days, self.acclimation.increment_min]);
+        writer.put(&self.light.lamp_life_h.to_le_bytes());
+        writer.put(&self.light.full_lux.to_le_bytes());
+        writer.put(&[self.light.closed_loop as u8]);
+        writer.put(&[self.display.page_s, self.display.night_sleep as u8]);
+        writer.put(&[self.dosing.daily_ml, self.dosing.doses_per_day]);
+        writer.put(&self.filter.pause_before_s.to_le_bytes());
+        writer.put(&self.filter.pause_after_s.to_le_bytes());
+        writer.wave_settings(&self.wave.day);
+        writer.wave_settings(&self.wave.night);
+    }
+
+    pub fn get(&self, key: ConfigKey) -> i32 {
+        match key {
+            ConfigKey::DayS => self.day_s as i32,
+            ConfigKey::LightOnS => self.light.on_duration_s as i32,
+            ConfigKey::FeedAngleDdeg => self.feeder.delivery_angle_ddeg as i32,
+            ConfigKey::FeedSpeedDegS => self.feeder.delivery_speed_deg_s as i32,
+            ConfigKey::VibrationAmplDdeg => self.feeder.vibration_ampl_ddeg as i32,
+            ConfigKey::VibrationSpeedDegS => self.feeder.vibration_speed_deg_s as i32,
+            ConfigKey::VibrationCount => self.feeder.vibration_count as i32,
+            ConfigKey::AliveToggleMs => self.alive.toggle_ms as i32,
+            ConfigKey::AliveOffMs => self.alive.off_ms as i32,
+            ConfigKey::AliveToggleCount => self.alive.toggle_count as i32,
+            ConfigKey::BuzzerMuted => self.buzzer.muted as i32,
+            ConfigKey::QuietStartH => self.buzzer.quiet_start_h as i32,
+            ConfigKey::QuietEndH => self.buzzer.quiet_end_h as i32,
+            ConfigKey::LightSun => self.light.follow_sun as i32,
+            ConfigKey::SunLatCdeg => self.light.latitude_cdeg as i32,
+            ConfigKey::SunLonCdeg => self.light.longitude_cdeg as i32,
+            ConfigKey::UtcOffsetMin => self.light.utc_offset_min as i32,
+            ConfigKey::LightMinMin => self.light.min_on_min as i32,
+            ConfigKey::LightMaxMin => self.light.max_on_min as i32,
+            ConfigKey::MoonMaxPct => self.light.moon_max_pct as i32,
+            ConfigKey::CloudDepthPct => self.weather.cloud_depth_pct as i32,
+            ConfigKey::CloudsPerH => self.weather.clouds_per_h as i32,
+            ConfigKey::CloudMaxS => self.weather.cloud_max_s as i32,
+            ConfigKey::StormPct => self.weather.storm_pct as i32,
+            ConfigKey::StormMaxMin => self.weather.storm_max_min as i32,
+            ConfigKey::WeatherDays => self.weather.days as i32,
+            ConfigKey::WeatherSeed => self.weather.seed as i32,
+            ConfigKey::AcclimStartMin => self.acclimation.start_min as i32,
+            ConfigKey::AcclimEndMin => self.acclimation.end_min as i32,
+            ConfigKey::AcclimDays => self.acclimation.days as i32,
+            ConfigKey::AcclimStepMin => self.acclimation.increment_min as i32,
+            ConfigKey::LampLifeH => self.light.lamp_life_h as i32,
+            ConfigKey::LampLux => self.light.full_lux as i32,
+            ConfigKey::LightLoop => self.light.closed_loop as i32,
+            ConfigKey::DisplayPageS => self.display.page_s as i32,
+            ConfigKey::DisplaySleep => self.display.night_sleep as i32,
+            ConfigKey::DoseDailyMl => self.dosing.daily_ml as i32,
+            ConfigKey::DosesPerDay => self.dosing.doses_per_day as i32,
+            ConfigKey::FilterBeforeS => self.filter.pause_before_s as i32,
+            ConfigKey::FilterAfterS => self.filter.pause_after_s as i32,
+            ConfigKey::WaveDayPattern => self.wave.day.pattern.code() as i32,
+            ConfigKey::WaveDayMinPct => self.wave.day.min_power_pct as i32,
+            ConfigKey::WaveDayMaxPct => self.wave.day.max_power_pct as i32,
+            ConfigKey::WaveDayPeriodS => self.wave.day.period_s as i32,
+            ConfigKey::WaveNightPattern => self.wave.night.pattern.code() as i32,
+            ConfigKey::WaveNightMinPct => self.wave.night.min_power_pct as i32,
+            ConfigKey::WaveNightMaxPct => self.wave.night.max_power_pct as i32,
+            ConfigKey::WaveNightPeriodS => self.wave.night.period_s as i32,
+        }
+    }
+
+    /// Returns false if the value is out of range
+    pub fn set(&mut self, key: ConfigKey, value: i32) -> bool {
+        let u16_value = u16::try_from(value).ok().filter(|value| *value > 0);
+        let u8_value = u8::try_from(value).ok().filter(|value| *value > 0);
+
+        match (key, u16_value, u8_value) {
+            // The feeding and the doses run at each day start
+            (ConfigKey::DayS, _, _)
+                if value >= Self::MIN_DAY_S as i32 && value >= self.light.on_duration_s as i32 =>
+            {
+                self.day_s = value as u32
+            }
+            (ConfigKey::LightOnS, _, _) if (0..=self.day_s as i32).contains(&value) => {
+                self.light.on_duration_s = value as u32
+            }
+            (ConfigKey::FeedAngleDdeg, Some(value), _) if value <= FeederConfig::MAX_ANGLE_DDEG => {
+                self.feeder.delivery_angle_ddeg = value
+            }
+            (ConfigKey::FeedSpeedDegS, Some(value), _)
+                if (FeederConfig::MIN_SPEED_DEG_S..=FeederConfig::MAX_SPEED_DEG_S)
+                    .contains(&value) =>
+            {
+                self.feeder.delivery_speed_deg_s = value
+            }
+            (ConfigKey::VibrationAmplDdeg, Some(value), _)
+                if value <= FeederConfig::MAX_VIBRATION_AMPL_DDEG =>
+            {
+                self.feeder.vibration_ampl_ddeg = value
+            }
+            (ConfigKey::VibrationSpeedDegS, Some(value), _)
+                if (FeederConfig::MIN_SPEED_DEG_S..=FeederConfig::MAX_SPEED_DEG_S)
+                    .contains(&value) =>
+            {
+                self.feeder.vibration_speed_deg_s = value
+            }
+            (ConfigKey::VibrationCount, _, Some(value)) => self.feeder.vibration_count = value,
+            (ConfigKey::AliveToggleMs, Some(value), _) => self.alive.toggle_ms = value,
+            (ConfigKey::AliveOffMs, Some(value), _) => self.alive.off_ms = value,
+            (ConfigKey::AliveToggleCount, _, Some(value)) => self.alive.toggle_count = value,
+            (ConfigKey::BuzzerMuted, _, _) if (0..=1).contains(&value) => {
+                self.buzzer.muted = value == 1
+            }
+            (ConfigKey::QuietStartH, _, _) if (0..24).contains(&value) => {
+                self.buzzer.quiet_start_h = value as u8
+            }
+            (ConfigKey::QuietEndH, _, _) if (0..24).contains(&value) => {
+                self.buzzer.quiet_end_h = value as u8
+            }
+            (ConfigKey::LightSun, _, _) if (0..=1).contains(&value) => {
+                self.light.follow_sun = value == 1
+            }
+            (ConfigKey::SunLatCdeg, _, _) if (-9_000..=9_000).contains(&value) => {
+                self.light.latitude_cdeg = value as i16
+            }
+            (ConfigKey::SunLonCdeg, _, _) if (-18_000..=18_000).contains(&value) => {
+                self.light.longitude_cdeg = value as i16
+            }
+            (ConfigKey::UtcOffsetMin, _, _) if (-14 * 60..=14 * 60).contains(&value) => {
+                self.light.utc_offset_min = value as i16
+            }
+            (ConfigKey::LightMinMin, _, _)
+                if (0..=self.light.max_on_min as i32).contains(&value) =>
+            {
+                self.light.min_on_min = value as u16
+            }
+            (ConfigKey::LightMaxMin, _, _)
+                if (self.light.min_on_min as i32..=24 * 60).contains(&value) =>
+            {
+                self.light.max_on_min = value as u16
+            }
+            (ConfigKey::MoonMaxPct, _, _) if (0..=100).contains(&value) => {
+                self.light.moon_max_pct = value as u8
+            }
+            (ConfigKey::CloudDepthPct, _, _) if (0..=90).contains(&value) => {
+                self.weather.cloud_depth_pct = value as u8
+            }
+            // At least 2.5min between clouds on average
+            (ConfigKey::CloudsPerH, _, Some(value)) if value <= 12 => {
+                self.weather.clouds_per_h = value
+            }
+            (ConfigKey::CloudMaxS, Some(value), _) if (5..=600).contains(&value) => {
+                self.weather.cloud_max_s = value
+            }
+            (ConfigKey::StormPct, _, _) if (0..=100).contains(&value) => {
+                self.weather.storm_pct = value as u8
+            }
+            (ConfigKey::StormMaxMin, _, Some(value)) if (5..=60).contains(&value) => {
+                self.weather.storm_max_min = value
+            }
+            (ConfigKey::WeatherDays, _, _) if (0..=0b111_1111).contains(&value) => {
+                self.weather.days = value as u8
+            }
+            (ConfigKey::WeatherSeed, _, _) if (0..=u16::MAX as i32).contains(&value) => {
+                self.weather.seed = value as u16
+            }
+            (ConfigKey::AcclimStartMin, Some(value), _) if value <= self.acclimation.end_min => {
+                self.acclimation.start_min = value
+            }
+            (ConfigKey::AcclimEndMin, _, _)
+                if (self.acclimation.start_min as i32..=24 * 60).contains(&value) =>
+            {
+                self.acclimation.end_min = value as u16
+            }
+            (ConfigKey::AcclimDays, _, Some(value)) => self.acclimation.days = value,
+            (ConfigKey::AcclimStepMin, _, _) if (0..=u8::MAX as i32).contains(&value) => {
+                self.acclimation.increment_min = value as u8
+            }
+            (ConfigKey::LampLifeH, _, _) if (0..=u16::MAX as i32).contains(&value) => {
+                self.light.lamp_life_h = value as u16
+            }
+            (ConfigKey::LampLux, _, _) if (0..=u16::MAX as i32).contains(&value) => {
+                self.light.full_lux = value as u16
+            }
+            (ConfigKey::LightLoop, _, _) if (0..=1).contains(&value) => {
+                self.light.closed_loop = value == 1
+            }
+            (ConfigKey::DisplayPageS, _, _) if (0..=u8::MAX as i32).contains(&value) => {
+                self.display.page_s = value as u8
+            }
+            (ConfigKey::DisplaySleep, _, _) if (0..=1).contains(&value) => {
+                self.display.night_sleep = value == 1
+            }
+            (ConfigKey::DoseDailyMl, _, _)
+                if (0..=DosingConfig::MAX_DAILY_ML as i32).contains(&value) =>
+            {
+                self.dosing.daily_ml = value as u8
+            }
+            (ConfigKey::DosesPerDay, _, Some(value))
+                if value <= DosingConfig::MAX_DOSES_PER_DAY =>
+            {
+                self.dosing.doses_per_day = value
+            }
+            (ConfigKey::FilterBeforeS, _, _)
+                if (0..=FilterConfig::MAX_PAUSE_S as i32).contains(&value) =>
+            {
+                self.filter.pause_before_s = value as u16
+            }
+            (ConfigKey::FilterAfterS, _, _)
+                if (0..=FilterConfig::MAX_PAUSE_S as i32).contains(&value) =>
+            {
+                self.filter.pause_after_s = value as u16
+            }
+            (ConfigKey::WaveDayPattern | ConfigKey::WaveNightPattern, _, _) => {
+                match u8::try_from(value).ok().and_then(WavePattern::from_code) {
+                    Some(pattern) => self.wave_settings_mut(key).pattern = pattern,
+                    None => return false,
+                }
+            }
+            (ConfigKey::WaveDayMinPct | ConfigKey::WaveNightMinPct, _, _)
+                if (0..=self.wave_settings_mut(key).max_power_pct as i32).contains(&value) =>
+            {
+                self.wave_settings_mut(key).min_power_pct = value as u8
+            }
+            (ConfigKey::WaveDayMaxPct | ConfigKey::WaveNightMaxPct, _, _)
+                if (self.wave_settings_mut(key).min_power_pct as i32..=100).contains(&value) =>
+            {
+                self.wave_settings_mut(key).max_power_pct = value as u8
+            }
+            (ConfigKey::WaveDayPeriodS | ConfigKey::WaveNightPeriodS, Some(value), _)
+                if (WaveConfig::MIN_PERIOD_S..=WaveConfig::MAX_PERIOD_S).contains(&value) =>
+            {
+                self.wave_settings_mut(key).period_s = value
+            }
+            _ => return false,
+        }
+
+        true
+    }
+
+    /// Day or night settings of a wave key
+    fn wave_settings_mut(&mut self, key: ConfigKey) -> &mut WaveSettings {
+        match key {
+            ConfigKey::WaveNightPattern
+            | ConfigKey::WaveNightMinPct
+            | ConfigKey::WaveNightMaxPct
+            | ConfigKey::WaveNightPeriodS => &mut self.wave.night,
+            _ => &mut self.wave.day,
+        }
+    }
+
+    pub fn report(&self, serial: &mut Serial) {
+        for key in ConfigKey::ALL {
+            ufmt::uwriteln!(serial, "{} = {}\r", key.name(), self.get(key)).unwrap();
+        }
+    }
+
+    pub fn day_us(&self) -> u64 {
+        (self.day_s as u64) * 1_000_000
+    }
+}
@@ -0,0 +1,267 @@
+use super::{
+    clock::DateTime,
+    config::ConfigKey,
+    schedule::{CatchUp, ScheduleAction, ScheduleRule, Weekdays},
+    Serial,
+};
+use arduino_hal::prelude::*;
+
+pub enum Command {
+    /// "ph": print the last pH reading
+    Ph,
+    /// "ph cal": start the guided pH probe calibration
+    PhCalibrate,
+    /// "dose": print the dosing pump status
+    Dose,
+    /// "dose cal": run the dosing pump for the calibration time
+    DoseCalibrate,
+    /// "dose ml <ml>": volume measured after "dose cal"
+    DoseMeasured(u16),
+    /// "dose refill <ml>": the dosing container has been refilled
+    DoseRefill(u16),
+    /// "config": print the configuration
+    Config,
+    /// "config reset": restore the default configuration
+    ConfigReset,
+    /// "set <key> <value>": change and save a configuration value (may be negative)
+    ConfigSet(ConfigKey, i32),
+    /// "log": dump the event log
+    Log,
+    /// "log clear": erase the event log
+    LogClear,
+    /// "time": print the date and time
+    Time,
+    /// "time YYYY-MM-DD HH:MM:SS": set the date and time (seconds since 2000-01-01)
+    TimeSet(u32),
+    /// "sun": print today's sunrise and sunset (light following the sun) and the moon
+    Sun,
+    /// "status": print the time, light, acclimation and lamp hours
+    Status,
+    /// "acclim start": start the photoperiod acclimation from the next day
+    AcclimationStart,
+    /// "acclim stop": end the acclimation (the configured photoperiod applies)
+    AcclimationStop,
+    /// "lamp": print the lamp hour meter
+    Lamp,
+    /// "lamp reset": restart the lamp hour meter (lamp replaced)
+    LampReset,
+    /// "lux": print the light sensor reading
+    Lux,
+    /// "i2c": list the devices answering on the I2C bus
+    I2cScan,
+    /// "alarms": list the shown alarms
+    Alarms,
+    /// "tasks": print the task run times
+    Tasks,
+    /// "sched": list the weekly schedule
+    Schedule,
+    /// "sched add <MTWTFSS> <HH:MM> <feed|dose|light> <portions|ml|hours> <late|skip>", e.g.
+    /// "sched add MTWTF.. 08:30 feed 1 late": '.' for the days off
+    ScheduleAdd(ScheduleRule),
+    /// "sched del <n>": remove the rule n
+    ScheduleRemove(u8),
+    /// "ack": acknowledge the alarms (and release the top-off lockout)
+    Ack,
+    /// "ok": the requested step is ready (e.g. probe in the buffer solution)
+    Ok,
+    /// "done": end a procedure early (e.g. two-point pH calibration)
+    Done,
+    /// "abort": cancel the running procedure
+    Abort,
+    Unknown,
+}
+
+/// Line based serial console: commands end with '\r' or '\n'
+pub struct Console {
+    line: [u8; Self::LINE_MAX_LEN],
+    len: usize,
+}
+
+impl Console {
+    const LINE_MAX_LEN: usize = 40;
+
+    pub fn new() -> Self {
+        Self {
+            line: [0; Self::LINE_MAX_LEN],
+            len: 0,
+        }
+    }
+
+    /// Non blocking: consumes the received bytes, returns a command once a full line is received
+    pub fn poll(&mut self, serial: &mut Serial) -> Option<Command> {
+        while let Ok(byte) = serial.read() {
+            match byte {
+                b'\r' | b'\n' => {
+                    if self.len > 0 {
+                        let command = Self::parse(&self.line[..self.len]);
+                        self.len = 0;
+
+                        return Some(command);
+                    }
+                }
+                _ => {
+                    // Too long lines are truncated: they end up as unknown commands
+                    if self.len < Self::LINE_MAX_LEN {
+                        self.line[self.len] = byte;
+                        self.len += 1;
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    fn parse(line: &[u8]) -> Command {
+        match line {
+            b"ph" => Command::Ph,
+            b"ph cal" => Command::PhCalibrate,
+            b"dose" => Command::Dose,
+            b"dose cal" => Command::DoseCalibrate,
+            b"config" => Command::Config,
+            b"config reset" => Command::ConfigReset,
+            b"log" => Command::Log,
+            b"log clear" => Command::LogClear,
+            b"time" => Command::Time,
+            b"sun" => Command::Sun,
+            b"status" => Command::Status,
+            b"acclim start" => Command::AcclimationStart,
+            b"acclim stop" => Command::AcclimationStop,
+            b"lamp" => Command::Lamp,
+            b"lamp reset" => Command::LampReset,
+            b"lux" => Command::Lux,
+            b"i2c" => Command::I2cScan,
+            b"alarms" => Command::Alarms,
+            b"ack" => Command::Ack,
+            b"tasks" => Command::Tasks,
+            b"sched" => Command::Schedule,
+            b"ok" => Command::Ok,
+            b"done" => Command::Done,
+            b"abort" => Command::Abort,
+            _ => {
+                if let Some(ml) = Self::parse_u16_argument(line, b"dose ml ") {
+                    Command::DoseMeasured(ml)
+                } else if let Some(ml) = Self::parse_u16_argument(line, b"dose refill ") {
+                    Command::DoseRefill(ml)
+                } else if let Some(command) = Self::parse_config_set(line) {
+                    command
+                } else if let Some(rule) = Self::parse_schedule_add(line) {
+                    Command::ScheduleAdd(rule)
+                } else if let Some(index) = Self::parse_u16_argument(line, b"sched del ") {
+                    Command::ScheduleRemove(u8::try_from(index).unwrap_or(u8::MAX))
+                } else if let Some(epoch_s) = line
+                    .strip_prefix(b"time ")
+                    .and_then(DateTime::parse)
+                    .and_then(|date_time| date_time.to_epoch_s())
+                {
+                    Command::TimeSet(epoch_s)
+                } else {
+                    Command::Unknown
+                }
+            }
+        }
+    }
+
+    /// "set <key> <value>"
+    fn parse_config_set(line: &[u8]) -> Option<Command> {
+        let arguments = line.strip_prefix(b"set ")?;
+        let separator = arguments.iter().position(|byte| *byte == b' ')?;
+
+        let key = ConfigKey::from_name(&arguments[..separator])?;
+        let value = match &arguments[separator + 1..] {
+            [b'-', digits @ ..] => -i32::try_from(Self::parse_number(digits)?).ok()?,
+            digits => i32::try_from(Self::parse_number(digits)?).ok()?,
+        };
+
+        Some(Command::ConfigSet(key, value))
+    }
+
+    /// "sched add <MTWTFSS> <HH:MM> <feed|dose|light> <portions|ml|hours> <late|skip>"
+    fn parse_schedule_add(line: &[u8]) -> Option<ScheduleRule> {
+        let mut arguments = line
+            .strip_prefix(b"sched add ")?
+            .split(|byte| *byte == b' ');
+
+        let weekdays = Weekdays::parse(arguments.next()?)?;
+
+        let time = arguments.next()?;
+        if time.len() != 5 || time[2] != b':' {
+            return None;
+        }
+        let hour = Self::parse_number(&time[..2]).filter(|hour| *hour < 24)?;
+        let minute = Self::parse_number(&time[3..]).filter(|minute| *minute < 60)?;
+
+        let action = arguments.next()?;
+        let parameter = Self::parse_number(arguments.next()?)
+            .and_then(|value| u8::try_from(value).ok())
+            .filter(|value| *value > 0)?;
+        let action = match action {
+            b"feed" if parameter <= ScheduleAction::MAX_PORTIONS => ScheduleAction::Feed {
+                portions: parameter,
+            },
+            b"dose" => ScheduleAction::Dose { ml: parameter },
+            b"light" if parameter <= ScheduleAction::MAX_LIGHT_HOURS => {
+                ScheduleAction::Light { hours: parameter }
+            }
+            _ => return None,
+        };
+
+        let catch_up = match arguments.next()? {
+            b"late" => CatchUp::RunLate,
+            b"skip" => CatchUp::Skip,
+            _ => return None,
+        };
+
+        if arguments.next().is_some() {
+            return None;
+        }
+
+        Some(ScheduleRule {
+            weekdays,
+            minute_of_day: (hour * 60 + minute) as u16,
+            action,
+            catch_up,
+        })
+    }
+
+    /// "<prefix><decimal number>"
+    fn parse_u16_argument(line: &[u8], prefix: &[u8]) -> Option<u16> {
+        let value = Self::parse_number(line.strip_prefix(prefix)?)?;
+
+        u16::try_from(value).ok()
+    }
+
+    fn parse_number(digits: &[u8]) -> Option<u32> {
+        if digits.is_empty() {
+            return None;
+        }
+
+        let mut value: u32 = 0;
+        for digit in digits {
+            if !digit.is_ascii_digit() {
+                return None;
+            }
+
+            value = value.checked_mul(10)?.checked_add((digit - b'0') as u32)?;
+        }
+
+        Some(value)
+    }
+}
+
+/// Writes a fixed point value with 2 decimals: 702 => "7.02"
+pub fn write_fixed_x100(serial: &mut Serial, value_x100: i32) {
+    if value_x100 < 0 {
+        ufmt::uwrite!(serial, "-").unwrap();
+    }
+
+    let value_x100 = value_x100.unsigned_abs();
+    let integer = value_x100 / 100;
+    let decimals = value_x100 % 100;
+
+    if decimals < 10 {
+        ufmt::uwrite!(serial, "{}.0{}", integer, decimals).unwrap();
+    } else {
+        ufmt::uwrite!(serial, "{}.{}", integer, decimals).unwrap();
+    }
+}
@@ -0,0 +1,316 @@
+use super::{
+    config::DosingConfig,
+    storage::{read_record, write_record},
+    task::{Task, TaskContext},
+    Serial,
+};
+use crate::drivers::time::timer::Timer;
+use arduino_hal::{
+    port::{mode::Output, Pin, PinOps},
+    Eeprom,
+};
+
+enum DosingState {
+    Idle,
+    Dosing { dose_ul: u32 },
+    Calibrating,
+}
+
+/// Peristaltic pump (DC motor through a relay): the volume is given by the run time
+pub struct DosingPump<PumpPin: PinOps> {
+    pump: Pin<Output, PumpPin>,
+    config: DosingConfig,
+    eeprom_addr: u16,
+    run_timer: Timer,
+    state: DosingState,
+    flow_ul_per_s: u16,
+    container_ml: u16,
+    // Kept in EEPROM with the day they belong to: a reset cannot dose twice
+    dosed_today_ul: u32,
+    next_dose_index: u8,
+    // Wall day (days since 2000-01-01) of the day start, NO_DAY if unknown
+    wall_day: u16,
+    // The day state read at boot is restored by the first day start
+    restore_day: bool,
+    inhibited: bool,
+}
+
+impl<PumpPin: PinOps> DosingPump<PumpPin> {
+    // [flow (u16), container (u16), wall day (u16), dosed today (u32), next dose]
+    const RECORD_LEN: usize = 2 + 2 + 2 + 4 + 1;
+    // Flow and container only (no day state)
+    const LEGACY_RECORD_LEN: usize = 4;
+    const NO_DAY: u16 = u16::MAX;
+
+    const DEFAULT_FLOW_UL_PER_S: u16 = 1_000; // 1 ml/s
+    const CALIBRATION_RUN_S: u32 = 60;
+
+    pub fn new(
+        mut pump_pin: Pin<Output, PumpPin>,
+        config: &DosingConfig,
+        eeprom: &Eeprom,
+        eeprom_addr: u16,
+    ) -> Self {
+        pump_pin.set_low();
+
+        let mut dosing_pump = Self {
+            pump: pump_pin,
+            config: *config,
+            eeprom_addr,
+            run_timer: Timer::new(0),
+            state: DosingState::Idle,
+            flow_ul_per_s: Self::DEFAULT_FLOW_UL_PER_S,
+            container_ml: 0,
+            dosed_today_ul: 0,
+            next_dose_index: 0,
+            wall_day: Self::NO_DAY,
+            restore_day: false,
+            inhibited: false,
+        };
+
+        let mut record = [0; Self::RECORD_LEN];
+        if read_record(eeprom, eeprom_addr, &mut record) {
+            dosing_pump.flow_ul_per_s = u16::from_le_bytes([record[0], record[1]]);
+            dosing_pump.container_ml = u16::from_le_bytes([record[2], record[3]]);
+            dosing_pump.wall_day = u16::from_le_bytes([record[4], record[5]]);
+            dosing_pump.dosed_today_ul =
+                u32::from_le_bytes([record[6], record[7], record[8], record[9]]);
+            dosing_pump.next_dose_index = record[10];
+            dosing_pump.restore_day = true;
+        } else if read_record(eeprom, eeprom_addr, &mut record[..Self::LEGACY_RECORD_LEN]) {
+            dosing_pump.flow_ul_per_s = u16::from_le_bytes([record[0], record[1]]);
+            dosing_pump.container_ml = u16::from_le_bytes([record[2], record[3]]);
+        }
+
+        dosing_pump
+    }
+
+    pub fn set_config(&mut self, config: &DosingConfig) {
+        self.config = *config;
+    }
+
+    /// Scheduled doses are skipped while inhibited (e.g. alarm safety action)
+    pub fn set_inhibited(&mut self, inhibited: bool) {
+        self.inhibited = inhibited;
+    }
+
+    pub fn is_container_empty(&self) -> bool {
+        (self.container_ml as u32) * 1_000 < self.dose_ul()
+    }
+
+    /// No more doses until the next day (e.g. after an unexpected reset: the day's
+    /// schedule restarts and would dose twice)
+    pub fn skip_day(&mut self) {
+        self.next_dose_index = self.config.doses_per_day;
+    }
+
+    /// Extra dose on top of the daily program, within the same safety limits
+    pub fn dose_extra(&mut self, t_us: u64, ml: u8, serial: &mut Serial) {
+        if !matches!(self.state, DosingState::Idle) {
+            ufmt::uwriteln!(serial, "Dosing pump busy\r").unwrap();
+            return;
+        }
+
+        self.start_dose(t_us, (ml as u32) * 1_000, serial);
+    }
+
+    /// Runs the pump for a fixed time: the measured volume gives the flow
+    pub fn start_calibration(&mut self, t_us: u64, serial: &mut Serial) {
+        if !matches!(self.state, DosingState::Idle) {
+            ufmt::uwriteln!(serial, "Dosing pump busy\r").unwrap();
+            return;
+        }
+
+        if (self.container_ml as u32) * 1_000 < self.calibration_ul() {
+            ufmt::uwriteln!(serial, "Dosing: container empty\r").unwrap();
+            return;
+        }
+
+        ufmt::uwriteln!(
+            serial,
+            "Dosing pump running for {}s into a measuring cup\r",
+            Self::CALIBRATION_RUN_S
+        )
+        .unwrap();
+
+        self.run_timer = Timer::new(Self::CALIBRATION_RUN_S as u64 * 1_000_000);
+        self.start_pump(t_us);
+        self.state = DosingState::Calibrating;
+    }
+
+    pub fn set_calibration(&mut self, measured_ml: u16, serial: &mut Serial, eeprom: &mut Eeprom) {
+        let flow_ul_per_s = (measured_ml as u32) * 1_000 / Self::CALIBRATION_RUN_S;
+
+        if flow_ul_per_s == 0 || flow_ul_per_s > u16::MAX as u32 {
+            ufmt::uwriteln!(serial, "Invalid volume\r").unwrap();
+            return;
+        }
+
+        self.flow_ul_per_s = flow_ul_per_s as u16;
+        self.save(eeprom);
+
+        ufmt::uwriteln!(serial, "Dosing flow: {} ul/s\r", self.flow_ul_per_s).unwrap();
+    }
+
+    /// The container has been refilled
+    pub fn set_container_level(&mut self, ml: u16, eeprom: &mut Eeprom) {
+        self.container_ml = ml;
+        self.save(eeprom);
+    }
+
+    pub fn report(&self, serial: &mut Serial) {
+        ufmt::uwriteln!(
+            serial,
+            "Dosing: {} ul today, container {} ml, flow {} ul/s\r",
+            self.dosed_today_ul,
+            self.container_ml,
+            self.flow_ul_per_s
+        )
+        .unwrap();
+    }
+
+    fn dose_time_of_day_us(&self, dose_index: u8, day_us: u64) -> u64 {
+        (dose_index as u64) * day_us / (self.config.doses_per_day as u64)
+    }
+
+    fn dose_ul(&self) -> u32 {
+        (self.config.daily_ml as u32) * 1_000 / (self.config.doses_per_day as u32)
+    }
+
+    /// Pumped by a calibration run, at the current flow
+    fn calibration_ul(&self) -> u32 {
+        (self.flow_ul_per_s as u32) * Self::CALIBRATION_RUN_S
+    }
+
+    /// Volume taken from the container, rounded up (ml)
+    fn debit_container(&mut self, volume_ul: u32) {
+        self.container_ml = self
+            .container_ml
+            .saturating_sub(((volume_ul + 999) / 1_000) as u16);
+    }
+
+    fn start_dose(&mut self, t_us: u64, dose_ul: u32, serial: &mut Serial) {
+        if self.inhibited {
+            ufmt::uwriteln!(serial, "Dosing: inhibited by an alarm\r").unwrap();
+            return;
+        }
+
+        if self.dosed_today_ul + dose_ul > (DosingConfig::MAX_DAILY_ML as u32) * 1_000 {
+            ufmt::uwriteln!(serial, "Dosing: daily limit reached\r").unwrap();
+            return;
+        }
+
+        if (self.container_ml as u32) * 1_000 < dose_ul {
+            // Running dry damages the pump tubing
+            ufmt::uwriteln!(serial, "Dosing: container empty\r").unwrap();
+            return;
+        }
+
+        let run_us = (dose_ul as u64) * 1_000_000 / (self.flow_ul_per_s as u64);
+
+        self.run_timer = Timer::new(run_us);
+        self.start_pump(t_us);
+        self.state = DosingState::Dosing { dose_ul };
+    }
+
+    fn start_pump(&mut self, t_us: u64) {
+        self.pump.set_high();
+        self.run_timer.start(t_us);
+    }
+
+    fn stop_pump(&mut self) {
+        self.pump.set_low();
+        self.run_timer.stop();
+        self.state = DosingState::Idle;
+    }
+
+    fn save(&self, eeprom: &mut Eeprom) {
+        let mut record = [0; Self::RECORD_LEN];
+        record[..2].copy_from_slice(&self.flow_ul_per_s.to_le_bytes());
+        record[2..4].copy_from_slice(&self.container_ml.to_le_bytes());
+        record[4..6].copy_from_slice(&self.wall_day.to_le_bytes());
+        record[6..10].copy_from_slice(&self.dosed_today_ul.to_le_bytes());
+        record[10] = self.next_dose_index;
+
+        write_record(eeprom, self.eeprom_addr, &record);
+    }
+}
+
+impl<PumpPin: PinOps> Task for DosingPump<PumpPin> {
+    fn name(&self) -> &'static str {
+        "dosing"
+    }
+
+    fn init(&mut self, context: &mut TaskContext) {
+        // The time reset breaks the run timer: a dose across midnight is cut
+        if !matches!(self.state, DosingState::Idle) {
+            self.stop_pump();
+        }
+
+        let wall_day = context
+            .wall_s
+            .map(|wall_s| (wall_s / 86_400) as u16)
+            .unwrap_or(Self::NO_DAY);
+
+        // After a boot, the doses already given today are not repeated. The clock is not
+        // kept across a reset: an unknown day is taken as the saved one (a power loss
+        // over the day start skips the rest of the saved day's doses)
+        let same_day = self.restore_day
+            && (wall_day == Self::NO_DAY
+                || self.wall_day == Self::NO_DAY
+                || wall_day == self.wall_day);
+        self.restore_day = false;
+
+        if !same_day {
+            self.dosed_today_ul = 0;
+            self.next_dose_index = 0;
+        }
+        if wall_day != Self::NO_DAY || !same_day {
+            self.wall_day = wall_day;
+        }
+        self.save(context.eeprom);
+    }
+
+    fn update(&mut self, context: &mut TaskContext) {
+        let t_us = context.t_us;
+
+        match self.state {
+            DosingState::Idle => {
+                if self.next_dose_index < self.config.doses_per_day
+                    && context.time_of_day_us
+                        >= self.dose_time_of_day_us(self.next_dose_index, context.day_us)
+                {
+                    self.next_dose_index += 1;
+                    if self.config.daily_ml > 0 {
+                        self.start_dose(t_us, self.dose_ul(), context.serial);
+                    }
+                    self.save(context.eeprom);
+                }
+            }
+            DosingState::Dosing { dose_ul } => {
+                if let Ok(true) = self.run_timer.has_expired(t_us) {
+                    self.stop_pump();
+
+                    self.dosed_today_ul += dose_ul;
+                    self.debit_container(dose_ul);
+                    self.save(context.eeprom);
+                }
+            }
+            DosingState::Calibrating => {
+                if let Ok(true) = self.run_timer.has_expired(t_us) {
+                    self.stop_pump();
+
+                    // Not counted in the daily volume: pumped into the measuring cup
+                    self.debit_container(self.calibration_ul());
+                    self.save(context.eeprom);
+
+                    ufmt::uwriteln!(
+                        context.serial,
+                        "Measure the pumped volume and send 'dose ml <ml>'\r"
+                    )
+                    .unwrap();
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,233 @@
+use super::{
+    alarm::AlarmId,
+    clock::{Clock, DateTime},
+    storage::{EVENT_LOG_ADDR, EVENT_LOG_LEN},
+    supervisor::WatchedTask,
+    Serial,
+};
+use arduino_hal::Eeprom;
+
+#[derive(Clone, Copy)]
+pub enum Event {
+    /// MCUSR reset flags
+    Boot {
+        reset_cause: u8,
+    },
+    Feeding,
+    LightOn,
+    LightOff,
+    /// Alarm raised (AlarmId)
+    Alarm {
+        code: u8,
+    },
+    ConfigChange,
+    ClockSet,
+    /// Task missing its watchdog check-in deadline
+    TaskStall {
+        task: u8,
+    },
+    /// Reset by the panic handler (see PanicRecord)
+    Panic,
+}
+
+impl Event {
+    fn encode(&self) -> (u8, u8) {
+        match *self {
+            Event::Boot { reset_cause } => (0, reset_cause),
+            Event::Feeding => (1, 0),
+            Event::LightOn => (2, 0),
+            Event::LightOff => (3, 0),
+            Event::Alarm { code } => (4, code),
+            Event::ConfigChange => (5, 0),
+            Event::ClockSet => (6, 0),
+            Event::TaskStall { task } => (7, task),
+            Event::Panic => (8, 0),
+        }
+    }
+
+    fn decode(kind: u8, data: u8) -> Option<Self> {
+        match kind {
+            0 => Some(Event::Boot { reset_cause: data }),
+            1 => Some(Event::Feeding),
+            2 => Some(Event::LightOn),
+            3 => Some(Event::LightOff),
+            4 => Some(Event::Alarm { code: data }),
+            5 => Some(Event::ConfigChange),
+            6 => Some(Event::ClockSet),
+            7 => Some(Event::TaskStall { task: data }),
+            8 => Some(Event::Panic),
+            _ => None,
+        }
+    }
+
+    fn write(&self, serial: &mut Serial) {
+        match *self {
+            Event::Boot { reset_cause } => {
+                ufmt::uwrite!(serial, "boot (MCUSR {})", reset_cause).unwrap()
+            }
+            Event::Feeding => ufmt::uwrite!(serial, "feeding").unwrap(),
+            Event::LightOn => ufmt::uwrite!(serial, "light on").unwrap(),
+            Event::LightOff => ufmt::uwrite!(serial, "light off").unwrap(),
+            Event::Alarm { code } => match AlarmId::from_index(code) {
+                Some(id) => ufmt::uwrite!(serial, "alarm: {}", id.name()).unwrap(),
+                None => ufmt::uwrite!(serial, "alarm {}", code).unwrap(),
+            },
+            Event::ConfigChange => ufmt::uwrite!(serial, "config change").unwrap(),
+            Event::ClockSet => ufmt::uwrite!(serial, "clock set").unwrap(),
+            Event::TaskStall { task } => match WatchedTask::from_index(task) {
+                Some(task) => ufmt::uwrite!(serial, "{} task stalled", task.name()).unwrap(),
+                None => ufmt::uwrite!(serial, "task {} stalled", task).unwrap(),
+            },
+            Event::Panic => ufmt::uwrite!(serial, "panic").unwrap(),
+        }
+    }
+}
+
+/// Ring buffer of 8 byte entries in EEPROM: [seq, kind, data, flags, timestamp (u32 LE)].
+///
+/// Wear leveling: entries are written in turn over the whole area, and the write position
+/// is not stored anywhere (it would wear a single cell): at boot it is found where the
+/// sequence numbers stop following each other.
+pub struct EventLog {
+    next_index: u16,
+    next_seq: u8,
+    is_full: bool,
+}
+
+impl EventLog {
+    const ENTRY_LEN: u16 = 8;
+    const ENTRY_COUNT: u16 = EVENT_LOG_LEN / Self::ENTRY_LEN; // Must stay < 256 (u8 seq)
+
+    const ERASED: u8 = 0xFF;
+    const FLAG_CLOCK_SET: u8 = 1 << 0;
+
+    pub fn new(eeprom: &Eeprom) -> Self {
+        let mut log = Self {
+            next_index: 0,
+            next_seq: 0,
+            is_full: false,
+        };
+
+        if Self::kind(eeprom, 0) == Self::ERASED {
+            return log;
+        }
+
+        let mut previous_seq = Self::seq(eeprom, 0);
+        for index in 1..Self::ENTRY_COUNT {
+            let seq = Self::seq(eeprom, index);
+
+            if Self::kind(eeprom, index) == Self::ERASED || seq != previous_seq.wrapping_add(1) {
+                log.next_index = index;
+                log.next_seq = previous_seq.wrapping_add(1);
+                log.is_full = Self::kind(eeprom, index) != Self::ERASED;
+
+                return log;
+            }
+
+            previous_seq = seq;
+        }
+
+        // The last entry has been written: the next one wraps to the start
+        log.next_seq = previous_seq.wrapping_add(1);
+        log.is_full = true;
+
+        log
+    }
+
+    pub fn record(&mut self, eeprom: &mut Eeprom, event: Event, clock: &Clock, t_us: u64) {
+        let (kind, data) = event.encode();
+        let flags = if clock.is_set() {
+            Self::FLAG_CLOCK_SET
+        } else {
+            0
+        };
+        let timestamp = clock.now_s(t_us).to_le_bytes();
+
+        let addr = Self::entry_addr(self.next_index);
+
+        // The sequence number is written last: it validates the entry
+        for (offset, byte) in [kind, data, flags]
+            .iter()
+            .chain(timestamp.iter())
+            .enumerate()
+        {
+            eeprom.write_byte(addr + 1 + offset as u16, *byte);
+        }
+        eeprom.write_byte(addr, self.next_seq);
+
+        self.next_seq = self.next_seq.wrapping_add(1);
+        self.next_index += 1;
+        if self.next_index == Self::ENTRY_COUNT {
+            self.next_index = 0;
+            self.is_full = true;
+        }
+    }
+
+    /// Oldest entry first
+    pub fn dump(&self, eeprom: &Eeprom, serial: &mut Serial) {
+        let (first_index, count) = if self.is_full {
+            (self.next_index, Self::ENTRY_COUNT)
+        } else {
+            (0, self.next_index)
+        };
+
+        ufmt::uwriteln!(serial, "Event log: {} entries\r", count).unwrap();
+
+        for i in 0..count {
+            let index = (first_index + i) % Self::ENTRY_COUNT;
+            let addr = Self::entry_addr(index);
+
+            let kind = eeprom.read_byte(addr + 1);
+            let data = eeprom.read_byte(addr + 2);
+            let flags = eeprom.read_byte(addr + 3);
+            let timestamp = u32::from_le_bytes([
+                eeprom.read_byte(addr + 4),
+                eeprom.read_byte(addr + 5),
+                eeprom.read_byte(addr + 6),
+                eeprom.read_byte(addr + 7),
+            ]);
+
+            if flags & Self::FLAG_CLOCK_SET != 0 {
+                DateTime::from_epoch_s(timestamp).write(serial);
+            } else {
+                ufmt::uwrite!(serial, "boot+{}s", timestamp).unwrap();
+            }
+            ufmt::uwrite!(serial, " ").unwrap();
+
+            match Event::decode(kind, data) {
+                Some(event) => event.write(serial),
+                None => ufmt::uwrite!(serial, "corrupted entry").unwrap(),
+            }
+            ufmt::uwriteln!(serial, "\r").unwrap();
+        }
+    }
+
+    pub fn clear(&mut self, eeprom: &mut Eeprom) {
+        // Only the written entries are erased
+        let end_index = if self.is_full {
+            Self::ENTRY_COUNT
+        } else {
+            self.next_index
+        };
+
+        for index in 0..end_index {
+            eeprom.erase_byte(Self::entry_addr(index) + 1);
+        }
+
+        self.next_index = 0;
+        self.next_seq = 0;
+        self.is_full = false;
+    }
+
+    fn entry_addr(index: u16) -> u16 {
+        EVENT_LOG_ADDR + index * Self::ENTRY_LEN
+    }
+
+    fn seq(eeprom: &Eeprom, index: u16) -> u8 {
+        eeprom.read_byte(Self::entry_addr(index))
+    }
+
+    fn kind(eeprom: &Eeprom, index: u16) -> u8 {
+        eeprom.read_byte(Self::entry_addr(index) + 1)
+    }
+}
@@ -1,9 +1,13 @@
+use super::{config::FeederConfig, supervisor::Supervisor};
 use crate::drivers::{
     stepper::{AngleSpeed, RotationAngleSpeed, Stepper},
     time::sys_timer::{ImplTimer, SysTimer},
 };
 use arduino_hal::port::{mode::Output, Pin, PinOps};
 
+// The watchdog is fed between the moves only: any configurable move must end before it fires
+const _: () = assert!(FeederConfig::MAX_MOVE_MS < Supervisor::WATCHDOG_TIMEOUT_MS);
+
 pub struct Feeder<
     StepperPinEnable: PinOps,
     StepperPinIn1: PinOps,
@@ -13,6 +17,7 @@ pub struct Feeder<
 > {
     enable_stepper_pin: Pin<Output, StepperPinEnable>,
     stepper_motor: Stepper<StepperPinIn1, StepperPinIn2, StepperPinIn3, StepperPinIn4>,
+    config: FeederConfig,
 }
 
 impl<
@@ -26,35 +31,42 @@ impl<
     const ENABLE_STEPPER_DELAY_US: u64 = 2_000_000; //2s
 
     const INIT_POS_SPEED_DEG_S: f32 = 15.0; // (deg/s)
-    const DELIVERY_SPEED_DEG_S: f32 = 35.0; // (deg/s)
-    const VIBRATION_SPEED_DEG_S: f32 = 35.0; // (deg/s)
 
     const INIT_ANTI_CLK_W_ANGLE_DEG: f32 = 45.0;
     const INIT_CLK_W_ANGLE_DEG: f32 = 10.0;
 
-    const DELIVERY_ANGLE_DEG: f32 = 22.5; // 360/16 = 22.5
-
-    const VIBRATION_AMPL_DEG: f32 = 13.0; // (deg)
-
     pub fn new(
         stepper_pin_en: Pin<Output, StepperPinEnable>,
         stepper_motor: Stepper<StepperPinIn1, StepperPinIn2, StepperPinIn3, StepperPinIn4>,
+        config: &FeederConfig,
     ) -> Self {
         Self {
             enable_stepper_pin: stepper_pin_en,
             stepper_motor,
+            config: *config,
         }
     }
 
-    pub fn init_position<TimerType: ImplTimer>(&mut self, sys_timer: &SysTimer<TimerType>) {
+    pub fn set_config(&mut self, config: &FeederConfig) {
+        self.config = *config;
+    }
+
+    /// Blocking: the supervisor is checked in after each move
+    pub fn init_position<TimerType: ImplTimer>(
+        &mut self,
+        sys_timer: &SysTimer<TimerType>,
+        supervisor: &mut Supervisor,
+    ) {
         self.enable_stepper_pin.set_high();
         sys_timer.delay_micros(Self::ENABLE_STEPPER_DELAY_US);
+        supervisor.blocking_step(sys_timer.micros());
 
         self.stepper_motor
             .rotate_by_angle(RotationAngleSpeed::AntiClockwise(AngleSpeed::new(
                 Self::INIT_ANTI_CLK_W_ANGLE_DEG,
                 Self::INIT_POS_SPEED_DEG_S,
             )));
+        supervisor.blocking_step(sys_timer.micros());
 
         self.stepper_motor
             .rotate_by_angle(RotationAngleSpeed::Clockwise(AngleSpeed::new(
@@ -65,24 +77,44 @@ impl<
         self.enable_stepper_pin.set_low();
     }
 
-    pub fn deliver_food<TimerType: ImplTimer>(&mut self, sys_timer: &SysTimer<TimerType>) {
+    /// Blocking: the supervisor is checked in after each move
+    pub fn deliver_food<TimerType: ImplTimer>(
+        &mut self,
+        sys_timer: &SysTimer<TimerType>,
+        supervisor: &mut Supervisor,
+    ) {
         self.enable_stepper_pin.set_high();
         sys_timer.delay_micros(Self::ENABLE_STEPPER_DELAY_US);
+        supervisor.blocking_step(sys_timer.micros());
 
         //Rotate
         self.stepper_motor
             .rotate_by_angle(RotationAngleSpeed::Clockwise(AngleSpeed::new(
-                Self::DELIVERY_ANGLE_DEG,
-                Self::DELIVERY_SPEED_DEG_S,
+                self.config.delivery_angle_ddeg as f32 / 10.0,
+                self.config.delivery_speed_deg_s as f32,
             )));
+        supervisor.blocking_step(sys_timer.micros());
 
         // Vibration
-        self.vibarte(Self::VIBRATION_AMPL_DEG, Self::VIBRATION_SPEED_DEG_S, 10);
+        self.vibarte(
+            self.config.vibration_ampl_ddeg as f32 / 10.0,
+            self.config.vibration_speed_deg_s as f32,
+            self.config.vibration_count as usize,
+            sys_timer,
+            supervisor,
+        );
 
         self.enable_stepper_pin.set_low();
     }
 
-    fn vibarte(&mut self, amplitude: f32, speed: f32, number: usize) {
+    fn vibarte<TimerType: ImplTimer>(
+        &mut self,
+        amplitude: f32,
+        speed: f32,
+        number: usize,
+        sys_timer: &SysTimer<TimerType>,
+        supervisor: &mut Supervisor,
+    ) {
         for _i in 0..number {
             self.stepper_motor
                 .rotate_by_angle(RotationAngleSpeed::Clockwise(AngleSpeed::new(
@@ -92,6 +124,7 @@ impl<
                 .rotate_by_angle(RotationAngleSpeed::AntiClockwise(AngleSpeed::new(
                     amplitude, speed,
                 )));
+            supervisor.blocking_step(sys_timer.micros());
         }
     }
 }
@@ -0,0 +1,112 @@
+use super::{
+    config::FilterConfig,
+    task::{Task, TaskContext},
+};
+use crate::drivers::time::timer::Timer;
+use arduino_hal::port::{mode::Output, Pin, PinOps};
+
+enum FilterState {
+    Running,
+    PausedBeforeFeeding,
+    PausedAfterFeeding,
+}
+
+/// Filter pump relay, wired on its normally closed contact: pin high => filter off.
+/// If the firmware resets (pins back to high impedance), the filter runs.
+pub struct Filter<RelayPin: PinOps> {
+    relay: Pin<Output, RelayPin>,
+    config: FilterConfig,
+    state: FilterState,
+    before_feeding_timer: Timer,
+    after_feeding_timer: Timer,
+    max_pause_timer: Timer,
+}
+
+impl<RelayPin: PinOps> Filter<RelayPin> {
+    // Hard limit, whatever the configured pauses
+    const MAX_PAUSE_US: u64 = (FilterConfig::MAX_PAUSE_S as u64) * 1_000 * 1_000;
+
+    pub fn new(mut relay_pin: Pin<Output, RelayPin>, config: &FilterConfig) -> Self {
+        relay_pin.set_low();
+
+        Self {
+            relay: relay_pin,
+            config: *config,
+            state: FilterState::Running,
+            before_feeding_timer: Timer::new(0),
+            after_feeding_timer: Timer::new(0),
+            max_pause_timer: Timer::new(Self::MAX_PAUSE_US),
+        }
+    }
+
+    /// Applied from the next pause
+    pub fn set_config(&mut self, config: &FilterConfig) {
+        self.config = *config;
+    }
+
+    pub fn pause_for_feeding(&mut self, t_us: u64) {
+        self.relay.set_high();
+        self.state = FilterState::PausedBeforeFeeding;
+
+        self.before_feeding_timer = Timer::new((self.config.pause_before_s as u64) * 1_000_000);
+        self.before_feeding_timer.start(t_us);
+        self.max_pause_timer.start(t_us);
+    }
+
+    /// The filter has been off long enough for the water to calm down
+    pub fn is_ready_for_feeding(&mut self, t_us: u64) -> bool {
+        match self.state {
+            FilterState::PausedBeforeFeeding => {
+                matches!(self.before_feeding_timer.has_expired(t_us), Ok(true))
+            }
+            // Hard limit reached: feeding anyway
+            FilterState::Running => true,
+            FilterState::PausedAfterFeeding => false,
+        }
+    }
+
+    pub fn feeding_done(&mut self, t_us: u64) {
+        self.before_feeding_timer.stop();
+
+        if let FilterState::PausedBeforeFeeding = self.state {
+            self.state = FilterState::PausedAfterFeeding;
+            self.after_feeding_timer = Timer::new((self.config.pause_after_s as u64) * 1_000_000);
+            self.after_feeding_timer.start(t_us);
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        matches!(self.state, FilterState::Running)
+    }
+
+    fn resume(&mut self) {
+        self.relay.set_low();
+        self.state = FilterState::Running;
+
+        self.before_feeding_timer.stop();
+        self.after_feeding_timer.stop();
+        self.max_pause_timer.stop();
+    }
+}
+
+impl<RelayPin: PinOps> Task for Filter<RelayPin> {
+    fn name(&self) -> &'static str {
+        "filter"
+    }
+
+    fn update(&mut self, context: &mut TaskContext) {
+        let t_us = context.t_us;
+
+        match self.state {
+            FilterState::Running => {}
+            FilterState::PausedBeforeFeeding | FilterState::PausedAfterFeeding => {
+                let resume = matches!(self.after_feeding_timer.has_expired(t_us), Ok(true))
+                    || matches!(self.max_pause_timer.has_expired(t_us), Ok(true));
+
+                if resume {
+                    self.resume();
+                }
+            }
+        }
+    }
+}
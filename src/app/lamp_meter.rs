@@ -0,0 +1,84 @@
+use super::{
+    storage::{read_record, write_record, LAMP_METER_ADDR},
+    Serial,
+};
+use arduino_hal::Eeprom;
+
+/// Lamp on-time weighted by the dimming level: 1h at half power counts 30min.
+/// Saved to EEPROM once per counted hour (~100k writes: decades)
+pub struct LampMeter {
+    minutes: u32,
+    saved_minutes: u32,
+    // Level x us not counted in minutes yet
+    level_us: u64,
+    level: u8,
+    t_last_us: u64,
+}
+
+impl LampMeter {
+    const RECORD_LEN: usize = 4;
+    // Counted at least this often, whatever the level changes
+    pub const UPDATE_PERIOD_US: u64 = 10 * 60 * 1_000_000; // 10min
+    const LEVEL_US_PER_MINUTE: u64 = (u8::MAX as u64) * 60 * 1_000_000;
+
+    pub fn new(eeprom: &Eeprom) -> Self {
+        let mut record = [0; Self::RECORD_LEN];
+        let minutes = if read_record(eeprom, LAMP_METER_ADDR, &mut record) {
+            u32::from_le_bytes(record)
+        } else {
+            0
+        };
+
+        Self {
+            minutes,
+            saved_minutes: minutes,
+            level_us: 0,
+            level: 0,
+            t_last_us: 0,
+        }
+    }
+
+    /// Counts the previous level up to now, then the new one from now.
+    /// The system time reset (day start) loses the time since the previous update
+    pub fn update(&mut self, t_us: u64, level: u8, eeprom: &mut Eeprom) {
+        self.level_us += (self.level as u64) * t_us.saturating_sub(self.t_last_us);
+        self.level = level;
+        self.t_last_us = t_us;
+
+        self.minutes += (self.level_us / Self::LEVEL_US_PER_MINUTE) as u32;
+        self.level_us %= Self::LEVEL_US_PER_MINUTE;
+
+        if self.minutes - self.saved_minutes >= 60 {
+            self.save(eeprom);
+        }
+    }
+
+    pub fn next_update_us(&self) -> u64 {
+        self.t_last_us + Self::UPDATE_PERIOD_US
+    }
+
+    pub fn hours(&self) -> u32 {
+        self.minutes / 60
+    }
+
+    /// After a lamp replacement
+    pub fn reset(&mut self, eeprom: &mut Eeprom) {
+        self.minutes = 0;
+        self.level_us = 0;
+        self.save(eeprom);
+    }
+
+    /// `lifetime_h`: 0 for no lifetime
+    pub fn report(&self, lifetime_h: u16, serial: &mut Serial) {
+        ufmt::uwrite!(serial, "Lamp: {}h", self.hours()).unwrap();
+        if lifetime_h > 0 {
+            ufmt::uwrite!(serial, " / {}h", lifetime_h).unwrap();
+        }
+        ufmt::uwriteln!(serial, " (full power equivalent)\r").unwrap();
+    }
+
+    fn save(&mut self, eeprom: &mut Eeprom) {
+        write_record(eeprom, LAMP_METER_ADDR, &self.minutes.to_le_bytes());
+        self.saved_minutes = self.minutes;
+    }
+}
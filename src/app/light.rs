@@ -1,32 +1,229 @@
-use crate::drivers::time::timer::Timer;
-use arduino_hal::port::{mode::Output, Pin, PinOps};
+use super::{
+    config::{LightConfig, WeatherConfig},
+    lamp_meter::LampMeter,
+    light_sensor::LightSensor,
+    moon::Moon,
+    task::{Task, TaskContext},
+    weather::Weather,
+    Serial,
+};
+use crate::drivers::{dimmer::Dimmer, time::timer::Timer};
+use arduino_hal::Eeprom;
 
-pub struct Light<LightPin: PinOps> {
+/// Light on/off times of day (us since the day start)
+#[derive(Clone, Copy)]
+pub struct Photoperiod {
+    pub on_us: u64,
+    pub off_us: u64,
+}
+
+/// Dimmable light: full power (weather effects aside) during the photoperiod, moonlight
+/// at night
+pub struct Light {
     timer: Timer,
-    pin: Pin<Output, LightPin>,
+    dimmer: Dimmer,
+    // Commanded level (the output may be trimmed by the light sensor)
+    level: u8,
+    on_duration_us: u64,
+    config: LightConfig,
+    is_day: bool,
+    moon_timer: Timer,
+    weather: Weather,
+    meter: LampMeter,
+    sensor: LightSensor,
 }
 
-impl<LightPin: PinOps> Light<LightPin> {
-    const LIGHT_ON_TIMEOUT_US: u64 = 7 * 60 * 60 * 1_000 * 1_000; // 7h
+impl Light {
+    // The moonlight changes slowly, its computation is heavy (float)
+    const MOON_UPDATE_US: u64 = 60 * 1_000_000; // 1min
+
+    pub fn new(
+        dimmer: Dimmer,
+        config: &LightConfig,
+        weather_config: &WeatherConfig,
+        meter: LampMeter,
+        sensor: LightSensor,
+    ) -> Self {
+        let on_duration_us = Self::on_duration_us(config);
 
-    pub fn new(light_pin: Pin<Output, LightPin>) -> Self {
         Self {
-            timer: Timer::new(Self::LIGHT_ON_TIMEOUT_US),
-            pin: light_pin,
+            timer: Timer::new(on_duration_us),
+            dimmer,
+            level: 0,
+            on_duration_us,
+            config: *config,
+            is_day: false,
+            moon_timer: Timer::new(Self::MOON_UPDATE_US),
+            weather: Weather::new(weather_config),
+            meter,
+            sensor,
+        }
+    }
+
+    /// The moonlight settings apply at its next update
+    pub fn set_config(&mut self, config: &LightConfig) {
+        self.config = *config;
+    }
+
+    /// Applied from the next day
+    pub fn set_weather_config(&mut self, config: &WeatherConfig) {
+        self.weather.set_config(config);
+    }
+
+    /// Applied from the next light cycle (day start): fixed or following the sun
+    pub fn set_on_duration_s(&mut self, on_duration_s: u32) {
+        self.on_duration_us = (on_duration_s as u64) * 1_000_000;
+    }
+
+    pub fn lamp_meter(&self) -> &LampMeter {
+        &self.meter
+    }
+
+    /// After a lamp replacement
+    pub fn reset_lamp_meter(&mut self, eeprom: &mut Eeprom) {
+        self.meter.reset(eeprom);
+    }
+
+    pub fn is_lamp_failed(&self) -> bool {
+        self.sensor.is_failed()
+    }
+
+    pub fn is_light_sensor_failed(&self) -> bool {
+        self.sensor.is_sensor_failed()
+    }
+
+    pub fn report_sensor(&self, serial: &mut Serial) {
+        self.sensor.report(&self.config, serial);
+    }
+
+    /// Output intensity, moonlight included
+    pub fn level_pct(&self) -> u8 {
+        ((self.dimmer.level() as u16) * 100 / (u8::MAX as u16)) as u8
+    }
+
+    /// Daylight (the moonlight does not count)
+    pub fn is_on(&self) -> bool {
+        self.is_day
+    }
+
+    /// The light is switched on at the day start
+    pub fn get_photoperiod(&self) -> Photoperiod {
+        Photoperiod {
+            on_us: 0,
+            off_us: self.on_duration_us,
         }
     }
 
-    pub fn init(&mut self, t_us: u64) {
-        self.pin.set_high();
-        self.timer.start(t_us);
+    fn on_duration_us(config: &LightConfig) -> u64 {
+        (config.on_duration_s as u64) * 1_000_000
     }
 
-    pub fn update(&mut self, t_us: u64) {
-        if let Ok(has_expired) = self.timer.has_expired(t_us) {
-            if has_expired {
-                self.pin.set_low();
-                self.timer.stop();
-            }
+    /// Moon up and clock set: max intensity weighted by the lit fraction of the moon
+    fn moonlight_level(&self, wall_s: Option<u32>) -> u8 {
+        let now_s = match wall_s {
+            Some(now_s) if self.config.moon_max_pct > 0 => now_s,
+            _ => return 0,
+        };
+
+        let moon = Moon::compute(now_s, &self.config);
+        if !moon.is_up(now_s % 86_400) {
+            return 0;
         }
+
+        let max_level = (self.config.moon_max_pct as f32) * (u8::MAX as f32) / 100.0;
+
+        (max_level * moon.illumination()) as u8
+    }
+
+    fn update_moonlight(&mut self, context: &TaskContext) {
+        self.set_level(self.moonlight_level(context.wall_s));
+        self.moon_timer.start(context.t_us);
+    }
+
+    fn set_level(&mut self, level: u8) {
+        self.level = level;
+        self.sensor.set_level(level);
+        self.dimmer
+            .set_level(self.sensor.output_level(level, &self.config));
+    }
+}
+
+impl Task for Light {
+    fn name(&self) -> &'static str {
+        "light"
+    }
+
+    fn init(&mut self, context: &mut TaskContext) {
+        self.timer = Timer::new(self.on_duration_us);
+
+        // A day started at night (following the sun) stays dark until the next day
+        if self.on_duration_us > 0 {
+            self.is_day = true;
+            self.set_level(u8::MAX);
+            self.timer.start(context.t_us);
+            self.moon_timer.stop();
+
+            let wall_day = context.wall_s.map(|now_s| now_s / 86_400);
+            self.weather
+                .start_day(context.t_us, wall_day, self.on_duration_us);
+        } else {
+            self.is_day = false;
+            self.timer.stop();
+            self.update_moonlight(context);
+        }
+
+        self.sensor.start(context.t_us);
+        self.meter
+            .update(context.t_us, self.dimmer.level(), context.eeprom);
+    }
+
+    fn update(&mut self, context: &mut TaskContext) {
+        let t_us = context.t_us;
+
+        if let Ok(true) = self.timer.has_expired(t_us) {
+            self.timer.stop();
+            self.is_day = false;
+            self.update_moonlight(context);
+        }
+
+        if let Ok(true) = self.moon_timer.has_expired(t_us) {
+            self.update_moonlight(context);
+        }
+
+        if self.is_day {
+            let level = self.weather.update(t_us);
+            self.set_level(level);
+        }
+
+        // The closed loop trim may have changed
+        let hold_trim = self.is_day && self.weather.is_active();
+        self.sensor
+            .update(t_us, &self.config, hold_trim, context.i2c);
+        self.dimmer
+            .set_level(self.sensor.output_level(self.level, &self.config));
+
+        // The lamp life is rated at daylight power: the moonlight is not counted
+        let day_level = if self.is_day { self.dimmer.level() } else { 0 };
+        self.meter.update(t_us, day_level, context.eeprom);
+    }
+
+    /// Nothing to do before the light goes off or the next weather effect, then at each
+    /// moonlight, lamp meter and light sensor update
+    fn next_wake_us(&self) -> Option<u64> {
+        let light_off_us = self.timer.get_expiry_us().unwrap_or(u64::MAX);
+        let moon_update_us = self.moon_timer.get_expiry_us().unwrap_or(u64::MAX);
+        let weather_us = if self.is_day {
+            self.weather.next_wake_us()?
+        } else {
+            u64::MAX
+        };
+
+        Some(
+            light_off_us
+                .min(moon_update_us)
+                .min(weather_us)
+                .min(self.meter.next_update_us())
+                .min(self.sensor.next_sample_us()),
+        )
     }
 }
@@ -0,0 +1,202 @@
+use super::{config::LightConfig, Serial};
+use crate::drivers::{bh1750::Bh1750, time::timer::Timer, twi::Twi};
+
+/// Lamp check: a BH1750 (I2C on A4/A5) facing the lamp is compared with the commanded level.
+/// The expected reading is proportional to the level (full_lux at full power). Closed loop:
+/// the output is trimmed until the reading matches instead.
+pub struct LightSensor {
+    sensor: Bh1750,
+    started: bool,
+    sample_timer: Timer,
+    level: u8,
+    // Level commanded since the previous sample, None if it changed (mixed reading)
+    window_level: Option<u8>,
+    lux: Option<u16>,
+    mismatch_count: u8,
+    failed: bool,
+    // Consecutive samples without a reading
+    missing_count: u8,
+    sensor_failed: bool,
+    trim_pct: u8,
+}
+
+impl LightSensor {
+    const SAMPLE_PERIOD_US: u64 = 1_000_000; // 1s
+
+    // Consecutive mismatching samples: a lamp failure (without reading: a sensor failure)
+    const FAILURE_SAMPLES: u8 = 30;
+
+    // Closed loop: 1% per sample, out of a deadband, down to a level with a usable reading
+    const TRIM_MIN_PCT: u8 = 50;
+    const TRIM_MAX_PCT: u8 = 200;
+    const TRIM_DEADBAND_PCT: u32 = 2;
+    const TRIM_MIN_LEVEL: u8 = 64;
+
+    pub fn new() -> Self {
+        Self {
+            sensor: Bh1750::new(Bh1750::ADDRESS_LOW),
+            started: false,
+            sample_timer: Timer::new(Self::SAMPLE_PERIOD_US),
+            level: 0,
+            window_level: None,
+            lux: None,
+            mismatch_count: 0,
+            failed: false,
+            missing_count: 0,
+            sensor_failed: false,
+            trim_pct: 100,
+        }
+    }
+
+    /// Lamp on (off) and the reading stays far below (above) the expected one
+    pub fn is_failed(&self) -> bool {
+        self.failed
+    }
+
+    /// Lamp check enabled and no reading from the sensor
+    pub fn is_sensor_failed(&self) -> bool {
+        self.sensor_failed
+    }
+
+    /// Output of a commanded level, trimmed in closed loop
+    pub fn output_level(&self, level: u8, config: &LightConfig) -> u8 {
+        if !Self::is_closed_loop(config) {
+            return level;
+        }
+
+        ((level as u32) * (self.trim_pct as u32) / 100).min(u8::MAX as u32) as u8
+    }
+
+    /// Commanded level (before the trim)
+    pub fn set_level(&mut self, level: u8) {
+        if level != self.level {
+            self.level = level;
+            self.window_level = None;
+        }
+    }
+
+    /// The first sample only opens the window
+    pub fn start(&mut self, t_us: u64) {
+        self.window_level = None;
+        self.sample_timer.start(t_us);
+    }
+
+    /// `hold_trim`: the light is dimmed on purpose (weather effect), the trim is kept
+    pub fn update(&mut self, t_us: u64, config: &LightConfig, hold_trim: bool, bus: &mut Twi) {
+        if !matches!(self.sample_timer.has_expired(t_us), Ok(true)) {
+            return;
+        }
+        self.sample_timer.start(t_us);
+
+        self.lux = self.read(bus);
+        let level = self.window_level;
+        self.window_level = Some(self.level);
+
+        if config.full_lux == 0 {
+            self.mismatch_count = 0;
+            self.failed = false;
+            self.missing_count = 0;
+            self.sensor_failed = false;
+            return;
+        }
+
+        // No reading: the lamp cannot be checked, the sensor failure is reported instead
+        let lux = match self.lux {
+            Some(lux) => {
+                self.missing_count = 0;
+                self.sensor_failed = false;
+                lux as u32
+            }
+            None => {
+                self.missing_count = self.missing_count.saturating_add(1);
+                if self.missing_count >= Self::FAILURE_SAMPLES {
+                    self.sensor_failed = true;
+                }
+                return;
+            }
+        };
+
+        let level = match level {
+            Some(level) => level,
+            None => return,
+        };
+
+        let expected_lux = Self::expected_lux(level, config);
+        let is_match =
+            lux >= expected_lux / 2 && lux <= expected_lux + (config.full_lux as u32) / 2;
+
+        if is_match {
+            self.mismatch_count = 0;
+            self.failed = false;
+        } else {
+            self.mismatch_count = self.mismatch_count.saturating_add(1);
+            if self.mismatch_count >= Self::FAILURE_SAMPLES {
+                self.failed = true;
+            }
+        }
+
+        if is_match && !hold_trim && level >= Self::TRIM_MIN_LEVEL && Self::is_closed_loop(config) {
+            let lux_pct = lux * 100 / expected_lux.max(1);
+            // Raised only while the output can follow: no windup at full power
+            let is_output_saturated =
+                (level as u32) * (self.trim_pct as u32) / 100 >= u8::MAX as u32;
+
+            if lux_pct + Self::TRIM_DEADBAND_PCT < 100 && !is_output_saturated {
+                self.trim_pct = (self.trim_pct + 1).min(Self::TRIM_MAX_PCT);
+            } else if lux_pct > 100 + Self::TRIM_DEADBAND_PCT {
+                self.trim_pct = (self.trim_pct - 1).max(Self::TRIM_MIN_PCT);
+            }
+        }
+    }
+
+    pub fn next_sample_us(&self) -> u64 {
+        self.sample_timer.get_expiry_us().unwrap_or(u64::MAX)
+    }
+
+    pub fn report(&self, config: &LightConfig, serial: &mut Serial) {
+        match self.lux {
+            Some(lux) => ufmt::uwrite!(serial, "Light sensor: {} lux", lux).unwrap(),
+            None => ufmt::uwrite!(serial, "Light sensor: no reading").unwrap(),
+        }
+
+        if config.full_lux == 0 {
+            ufmt::uwriteln!(serial, " (no lamp check)\r").unwrap();
+        } else {
+            ufmt::uwrite!(
+                serial,
+                ", expected {} lux",
+                Self::expected_lux(self.level, config)
+            )
+            .unwrap();
+            if Self::is_closed_loop(config) {
+                ufmt::uwrite!(serial, ", trim {}%", self.trim_pct).unwrap();
+            }
+            ufmt::uwriteln!(serial, "\r").unwrap();
+        }
+    }
+
+    fn is_closed_loop(config: &LightConfig) -> bool {
+        config.closed_loop && config.full_lux > 0
+    }
+
+    fn expected_lux(level: u8, config: &LightConfig) -> u32 {
+        (config.full_lux as u32) * (level as u32) / (u8::MAX as u32)
+    }
+
+    /// A sensor not responding is started again (power loss)
+    fn read(&mut self, bus: &mut Twi) -> Option<u16> {
+        if !self.started {
+            // First reading after the measurement time
+            self.started = self.sensor.start(bus).is_ok();
+            return None;
+        }
+
+        match self.sensor.read_lux(bus) {
+            Ok(lux) => Some(lux),
+            Err(_) => {
+                self.started = false;
+                None
+            }
+        }
+    }
+}
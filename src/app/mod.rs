@@ -1,54 +1,196 @@
+mod acclimation;
+mod alarm;
+mod alert;
 mod alive;
+mod clock;
+mod config;
+mod console;
+mod dosing;
+mod event_log;
 mod feeder;
+mod filter;
+mod lamp_meter;
 mod light;
+mod light_sensor;
+mod moon;
+mod panic_record;
+mod ph;
+mod photoperiod_relay;
+mod random;
+mod schedule;
+mod status_display;
+mod storage;
+mod sun;
+mod supervisor;
+mod task;
+mod top_off;
+mod wavemaker;
+mod weather;
 
 use crate::drivers::{
+    adc::Adc,
+    buzzer::Buzzer,
+    dimmer::Dimmer,
+    display::ROWS as DISPLAY_ROWS,
     stepper::{StepType, Stepper},
     time::{
-        sys_timer::{CtcTimer, SysTimer},
+        sys_timer::{FastPwmTimer, SysTimer},
         timer::Timer,
     },
+    twi::Twi,
 };
-use alive::AliveBeat;
+use acclimation::Acclimation;
+use alarm::{AckButton, AlarmAction, AlarmId, AlarmManager, Severity};
+use alert::{AlertPlayer, CRITICAL_MELODY, WARNING_MELODY};
+use alive::{AliveBeat, BeatPattern};
 use arduino_hal::{
-    hal::port::{PB0, PB1, PB2, PD0, PD1, PD5, PD6, PD7},
+    hal::{
+        port::{PB0, PB1, PB2, PB3, PB4, PC0, PC1, PC2, PC3, PD0, PD1, PD2, PD3, PD4, PD6, PD7},
+        wdt::Wdt,
+    },
     port::{
         mode::{AnyInput, Input, Output},
         Pin,
     },
-    Usart,
+    simple_pwm::{IntoPwmPin, Prescaler, Timer2Pwm},
+    Eeprom, Usart,
 };
 use avr_device::atmega328p::USART0;
+use clock::{write_time_of_day, Clock, DateTime};
+use config::{Config, ConfigStatus};
+use console::{Command, Console};
 use core::fmt::Arguments;
+use dosing::DosingPump;
+use event_log::{Event, EventLog};
 use feeder::Feeder;
+use filter::Filter;
+use lamp_meter::LampMeter;
 use light::Light;
+use light_sensor::LightSensor;
+use moon::Moon;
+use panic_record::PanicRecord;
+use ph::PhProbe;
+use photoperiod_relay::{LightEvent, PhotoperiodRelay, RelativeTime};
+use schedule::{Schedule, ScheduleAction};
+use status_display::{DisplayStatus, StatusDisplay};
+use storage::DOSING_PUMP_ADDR;
+use sun::Daylight;
+use supervisor::{ResetCause, Supervisor, WatchedTask};
+use task::{Scheduler, Task, TaskContext};
+use top_off::{LockoutCause, TopOff, TopOffState};
+use wavemaker::Wavemaker;
+
+pub type Serial = Usart<USART0, Pin<Input<AnyInput>, PD0>, Pin<Output, PD1>>;
 
 pub struct Application {
-    sys_timer: SysTimer<CtcTimer<16, 64, 250>>,
+    // Fast PWM: Timer/Counter0 also dims the light (OC0B)
+    sys_timer: SysTimer<FastPwmTimer<16, 64>>,
     alive: AliveBeat,
     day_timer: Timer,
-    light: Light<PD5>,
+    light: Light,
     feeder: Feeder<PD6, PD7, PB0, PB1, PB2>,
-    #[allow(dead_code)]
-    serial: Usart<USART0, Pin<Input<AnyInput>, PD0>, Pin<Output, PD1>>,
+    // Portions waiting for the filter to stop
+    pending_portions: u8,
+    // Restarted by the watchdog: the day-start actions already done are not repeated
+    watchdog_recovery: bool,
+    // Photoperiod of the next day set by the schedule (lost on a reset)
+    light_override_s: Option<u32>,
+    filter: Filter<PC3>,
+    wavemaker: Wavemaker<PD3, PB3>,
+    top_off: TopOff<PD2, PD4, PB4>,
+    ph_probe: PhProbe,
+    co2: PhotoperiodRelay<PC0>,
+    air_pump: PhotoperiodRelay<PC1>,
+    dosing_pump: DosingPump<PC2>,
+    adc: Adc,
+    // Shared by the I2C devices (lent with the task context)
+    i2c: Twi,
+    display: StatusDisplay,
+    config: Config,
+    clock: Clock,
+    event_log: EventLog,
+    schedule: Schedule,
+    acclimation: Acclimation,
+    supervisor: Supervisor,
+    alarms: AlarmManager,
+    ack_button: AckButton,
+    alert: AlertPlayer,
+    // One slot per task (see run_tasks)
+    scheduler: Scheduler<10>,
+    eeprom: Eeprom,
+    console: Console,
+    serial: Serial,
 }
 
 impl Application {
-    const DAY_US: u64 = 24 * 60 * 60 * 1_000 * 1_000; // 24h
+    const HOUR_S: i32 = 60 * 60;
+    // A day ending a little before the sunrise (rounding) still starts at sunrise
+    const SUNRISE_TOLERANCE_S: u32 = 10 * 60; // 10min
 
     pub fn new() -> Self {
         let dp = arduino_hal::Peripherals::take().unwrap();
         let pins = arduino_hal::pins!(dp);
 
-        let serial = arduino_hal::default_serial!(dp, pins, 9600);
+        // Reset flags, cleared for the next reset
+        let reset_cause = ResetCause::new(dp.CPU.mcusr.read().bits());
+        dp.CPU.mcusr.write(|w| unsafe { w.bits(0) });
+
+        // As early as possible: after a watchdog reset, the watchdog is still running
+        // with its shortest period
+        let mut supervisor = Supervisor::new(Wdt::new(dp.WDT, &dp.CPU.mcusr));
+
+        let mut serial = arduino_hal::default_serial!(dp, pins, 9600);
+
+        reset_cause.write(&mut serial);
+        if reset_cause.is_watchdog() {
+            ufmt::uwriteln!(&mut serial, "Watchdog reset: no feeding or dosing today\r").unwrap();
+        }
+
+        let adc = Adc::new(dp.ADC);
+
+        let mut eeprom = Eeprom::new(dp.EEPROM);
+
+        let panic_record = PanicRecord::take(&mut eeprom);
+        if let Some(panic_record) = &panic_record {
+            panic_record.report(&mut serial);
+        }
+
+        let (config, config_status) = Config::load(&mut eeprom);
+        match config_status {
+            ConfigStatus::Loaded => ufmt::uwriteln!(&mut serial, "Config loaded\r").unwrap(),
+            ConfigStatus::Migrated => ufmt::uwriteln!(&mut serial, "Config migrated\r").unwrap(),
+            ConfigStatus::Defaults => ufmt::uwriteln!(&mut serial, "Config defaults\r").unwrap(),
+        }
 
         // Digital pin 13 is also connected to an onboard LED marked "L"
-        let alive = AliveBeat::new(pins.d13.into_output());
+        let alive = AliveBeat::new(pins.d13.into_output(), &config.alive);
 
-        let mut sys_timer: SysTimer<CtcTimer<16, 64, 250>> = SysTimer::new(dp.TC0);
+        let mut sys_timer: SysTimer<FastPwmTimer<16, 64>> = SysTimer::new(dp.TC0);
 
         sys_timer.init();
 
+        let clock = Clock::new();
+
+        let mut event_log = EventLog::new(&eeprom);
+        event_log.record(
+            &mut eeprom,
+            Event::Boot {
+                reset_cause: reset_cause.bits(),
+            },
+            &clock,
+            sys_timer.micros(),
+        );
+        if panic_record.is_some() {
+            event_log.record(&mut eeprom, Event::Panic, &clock, sys_timer.micros());
+        }
+
+        // Latched until acknowledged
+        let mut alarms = AlarmManager::new();
+        if reset_cause.is_watchdog() || panic_record.is_some() {
+            alarms.raise(AlarmId::Reset, &mut serial);
+            alarms.clear(AlarmId::Reset, &mut serial);
+        }
+
         // Enable interrupts globally
         unsafe { avr_device::interrupt::enable() };
 
@@ -58,19 +200,103 @@ impl Application {
                 pins.d7.into_output(),
                 pins.d8.into_output(),
                 pins.d9.into_output(),
+                // Shared with the buzzer (OC1B)
                 pins.d10.into_output(),
                 StepType::Step8,
             ),
+            &config.feeder,
+        );
+
+        feeder.init_position(&sys_timer, &mut supervisor);
+
+        let top_off = TopOff::new(
+            pins.d2.into_pull_up_input(),
+            pins.d4.into_pull_up_input(),
+            pins.d12.into_output(),
+        );
+
+        let ph_probe = PhProbe::new(&eeprom);
+
+        let dosing_pump = DosingPump::new(
+            pins.a2.into_output(),
+            &config.dosing,
+            &eeprom,
+            DOSING_PUMP_ADDR,
+        );
+
+        // Timer2: 16MHz / 64 / 256 => ~976Hz PWM
+        let timer2 = Timer2Pwm::new(dp.TC2, Prescaler::Prescale64);
+
+        let wavemaker = Wavemaker::new(
+            pins.d3.into_output().into_pwm(&timer2),
+            Some(pins.d11.into_output().into_pwm(&timer2)),
+            &config.wave,
+        );
+
+        let alert = AlertPlayer::new(Buzzer::new(dp.TC1));
+
+        // CO2 injection from 1h before the light goes on to 1h before it goes off
+        let co2 = PhotoperiodRelay::new(
+            "CO2",
+            pins.a0.into_output(),
+            RelativeTime::new(LightEvent::LightOn, -Self::HOUR_S),
+            RelativeTime::new(LightEvent::LightOff, -Self::HOUR_S),
         );
 
-        feeder.init_position(&sys_timer);
+        // Air stone while the CO2 is off
+        let air_pump = PhotoperiodRelay::new(
+            "air pump",
+            pins.a1.into_output(),
+            RelativeTime::new(LightEvent::LightOff, -Self::HOUR_S),
+            RelativeTime::new(LightEvent::LightOn, -Self::HOUR_S),
+        );
+
+        // TWI on A4 (SDA) and A5 (SCL), internal pull-ups (external 4.7k recommended)
+        let mut i2c = Twi::new(
+            dp.TWI,
+            pins.a4.into_pull_up_input(),
+            pins.a5.into_pull_up_input(),
+            100_000,
+        );
+        let display = StatusDisplay::new(&config.display, &mut i2c);
 
         Self {
             sys_timer,
             alive,
-            day_timer: Timer::new(Self::DAY_US),
-            light: Light::new(pins.d5.into_output()),
+            day_timer: Timer::new(config.day_us()),
+            light: Light::new(
+                Dimmer::new(pins.d5.into_output()),
+                &config.light,
+                &config.weather,
+                LampMeter::new(&eeprom),
+                LightSensor::new(),
+            ),
             feeder,
+            pending_portions: 0,
+            watchdog_recovery: reset_cause.is_watchdog(),
+            light_override_s: None,
+            filter: Filter::new(pins.a3.into_output(), &config.filter),
+            wavemaker,
+            top_off,
+            ph_probe,
+            co2,
+            air_pump,
+            dosing_pump,
+            adc,
+            i2c,
+            display,
+            config,
+            clock,
+            event_log,
+            schedule: Schedule::new(&eeprom),
+            acclimation: Acclimation::new(&eeprom),
+            supervisor,
+            alarms,
+            ack_button: AckButton::new(),
+            alert,
+            scheduler: Scheduler::new(),
+            eeprom,
+            console: Console::new(),
             serial,
         }
     }
@@ -78,31 +304,531 @@ impl Application {
     pub fn update(&mut self) {
         let t_us = self.sys_timer.micros();
         if !self.day_timer.has_started() {
+            let sun_day = self.sun_day(t_us);
+            let (day_us, mut light_on_s) =
+                sun_day.unwrap_or((self.config.day_us(), self.config.light.on_duration_s));
+
+            let wall_day = self.clock.is_set().then(|| self.clock.now_s(t_us) / 86_400);
+            let acclimation_s = self.acclimation.new_day(
+                wall_day,
+                self.watchdog_recovery,
+                &self.config.acclimation,
+                &mut self.eeprom,
+            );
+            if let Some(acclimation_s) = acclimation_s {
+                // Following the sun, the acclimation caps the daylight
+                light_on_s = if sun_day.is_some() {
+                    light_on_s.min(acclimation_s)
+                } else {
+                    acclimation_s.min(self.config.day_s)
+                };
+            }
+            let light_override_s = self.light_override_s.take();
+            if let (Some(override_s), None) = (light_override_s, acclimation_s) {
+                light_on_s = override_s.min((day_us / 1_000_000) as u32);
+            }
+            self.day_timer = Timer::new(day_us);
             self.day_timer.start(t_us);
+            self.light.set_on_duration_s(light_on_s);
 
-            // Light
-            self.light.init(t_us);
+            self.run_tasks(t_us, true);
+            if self.light.is_on() {
+                self.log(Event::LightOn);
+            }
 
-            self.feeder.deliver_food(&self.sys_timer);
+            if self.watchdog_recovery {
+                // Safe restart: the food and the doses may have been delivered already
+                self.watchdog_recovery = false;
+                self.dosing_pump.skip_day();
+            } else if !self.schedule.has_feedings() {
+                self.request_feeding(t_us, 1);
+            }
 
-            self.alive.reset(t_us);
+            self.ack_button.init(t_us);
+            self.display.init(t_us);
         } else {
-            self.alive.update(&mut self.sys_timer);
+            self.update_alarms(t_us);
+            self.update_display(t_us);
 
-            self.light.update(t_us);
+            self.run_schedule(t_us);
+
+            // A portion per loop: the filter pause limit is checked between them
+            let delivered = self.pending_portions > 0 && self.filter.is_ready_for_feeding(t_us);
+            if delivered {
+                // D10 is shared by the buzzer and the stepper: the alarm resumes on the next loop
+                self.alert.play(None, t_us);
+                self.feeder
+                    .deliver_food(&self.sys_timer, &mut self.supervisor);
+                self.log(Event::Feeding);
+                self.pending_portions -= 1;
+
+                if self.pending_portions == 0 {
+                    self.filter.feeding_done(self.sys_timer.micros());
+                }
+            }
+            // Progress: a portion delivered or no food waiting for the filter
+            if delivered || self.pending_portions == 0 {
+                self.supervisor
+                    .check_in(WatchedTask::Feeder, self.sys_timer.micros());
+            }
+
+            // No flow while the filter is paused for feeding
+            self.wavemaker.set_feed_mode(!self.filter.is_running());
+            self.wavemaker.set_night_mode(!self.light.is_on());
+
+            let light_was_on = self.light.is_on();
+            self.run_tasks(t_us, false);
+            if light_was_on && !self.light.is_on() {
+                self.log(Event::LightOff);
+            }
+            let t_done_us = self.sys_timer.micros();
+            self.supervisor.check_in(WatchedTask::Alive, t_done_us);
+            self.supervisor.check_in(WatchedTask::Light, t_done_us);
+
+            if let Some(command) = self.console.poll(&mut self.serial) {
+                self.execute(command);
+                // The reports are written at the serial speed: the other tasks are held
+                self.supervisor.blocking_step(self.sys_timer.micros());
+            }
+            if self.ack_button.update(t_us, &mut self.adc) {
+                self.execute(Command::Ack);
+            }
+            self.supervisor
+                .check_in(WatchedTask::Serial, self.sys_timer.micros());
 
             if let Ok(has_expired) = self.day_timer.has_expired(t_us) {
                 if has_expired {
                     self.day_timer.stop();
 
+                    // The tasks are initialized for the new day at the next update
+                    self.clock.on_time_reset(self.sys_timer.micros());
                     self.sys_timer.reset_time();
                 }
             }
         }
 
+        if let Some(task) = self.supervisor.update(self.sys_timer.micros()) {
+            ufmt::uwriteln!(&mut self.serial, "{} task stalled: reset\r", task.name()).unwrap();
+            self.log(Event::TaskStall { task: task as u8 });
+        }
+
         // self.usb_debug(format_args!("Hello : {:?}", 1));
     }
 
+    fn execute(&mut self, command: Command) {
+        match command {
+            Command::Ph => self.ph_probe.report(&mut self.serial, None),
+            Command::PhCalibrate => self.ph_probe.start_calibration(&mut self.serial),
+            Command::Dose => self.dosing_pump.report(&mut self.serial),
+            Command::DoseCalibrate => {
+                let t_us = self.sys_timer.micros();
+                self.dosing_pump.start_calibration(t_us, &mut self.serial)
+            }
+            Command::DoseMeasured(ml) => {
+                self.dosing_pump
+                    .set_calibration(ml, &mut self.serial, &mut self.eeprom)
+            }
+            Command::DoseRefill(ml) => self.dosing_pump.set_container_level(ml, &mut self.eeprom),
+            Command::Config => self.config.report(&mut self.serial),
+            Command::ConfigReset => {
+                self.config = Config::default();
+                self.apply_config();
+            }
+            Command::ConfigSet(key, value) => {
+                if self.config.set(key, value) {
+                    self.apply_config();
+                } else {
+                    ufmt::uwriteln!(&mut self.serial, "Invalid value\r").unwrap();
+                }
+            }
+            Command::Log => self.event_log.dump(&self.eeprom, &mut self.serial),
+            Command::LogClear => {
+                self.event_log.clear(&mut self.eeprom);
+                ufmt::uwriteln!(&mut self.serial, "Event log cleared\r").unwrap();
+            }
+            Command::Time => self.clock.report(self.sys_timer.micros(), &mut self.serial),
+            Command::TimeSet(epoch_s) => {
+                self.clock.set(self.sys_timer.micros(), epoch_s);
+                self.log(Event::ClockSet);
+                self.align_day_to_sun();
+            }
+            Command::Sun => self.report_sun(),
+            Command::Status => self.report_status(),
+            Command::Lux => self.light.report_sensor(&mut self.serial),
+            Command::I2cScan => self.report_i2c_scan(),
+            Command::Lamp => self
+                .light
+                .lamp_meter()
+                .report(self.config.light.lamp_life_h, &mut self.serial),
+            Command::LampReset => {
+                self.light.reset_lamp_meter(&mut self.eeprom);
+                self.light
+                    .lamp_meter()
+                    .report(self.config.light.lamp_life_h, &mut self.serial);
+            }
+            Command::AcclimationStart => {
+                self.acclimation.start(&mut self.eeprom);
+                self.acclimation
+                    .report(&self.config.acclimation, &mut self.serial);
+            }
+            Command::AcclimationStop => {
+                self.acclimation.stop(&mut self.eeprom);
+                self.acclimation
+                    .report(&self.config.acclimation, &mut self.serial);
+            }
+            Command::Alarms => self.alarms.report(&mut self.serial),
+            Command::Tasks => self.scheduler.report(&mut self.serial),
+            Command::Schedule => self.schedule.report(&mut self.serial),
+            Command::ScheduleAdd(rule) => {
+                if !self.clock.is_set() {
+                    ufmt::uwriteln!(&mut self.serial, "Time not set\r").unwrap();
+                } else if self.schedule.add(
+                    rule,
+                    self.clock.now_s(self.sys_timer.micros()),
+                    &mut self.eeprom,
+                ) {
+                    self.schedule.report(&mut self.serial);
+                } else {
+                    ufmt::uwriteln!(
+                        &mut self.serial,
+                        "Schedule full ({} rules)\r",
+                        Schedule::MAX_RULES
+                    )
+                    .unwrap();
+                }
+            }
+            Command::ScheduleRemove(index) => {
+                if self.schedule.remove(index as usize, &mut self.eeprom) {
+                    self.schedule.report(&mut self.serial);
+                } else {
+                    ufmt::uwriteln!(&mut self.serial, "No such rule\r").unwrap();
+                }
+            }
+            Command::Ack => {
+                // The human check a top-off lockout waits for
+                self.top_off.clear_lockout();
+                self.alarms.acknowledge(&mut self.serial);
+            }
+            Command::Ok => self
+                .ph_probe
+                .calibration_next(&mut self.serial, &mut self.eeprom),
+            Command::Done => self
+                .ph_probe
+                .calibration_done(&mut self.serial, &mut self.eeprom),
+            Command::Abort => self
+                .ph_probe
+                .calibration_abort(&mut self.serial, &self.eeprom),
+            Command::Unknown => ufmt::uwriteln!(&mut self.serial, "Unknown command\r").unwrap(),
+        }
+    }
+
+    /// Saves the configuration and hands it to the modules: no reflash needed to tune them
+    fn apply_config(&mut self) {
+        self.config.save(&mut self.eeprom);
+
+        self.feeder.set_config(&self.config.feeder);
+        self.alive.set_config(&self.config.alive);
+        self.light.set_config(&self.config.light);
+        self.light.set_weather_config(&self.config.weather);
+        self.display.set_config(&self.config.display);
+        self.dosing_pump.set_config(&self.config.dosing);
+        self.filter.set_config(&self.config.filter);
+        self.wavemaker.set_config(&self.config.wave);
+        self.alive.reset(self.sys_timer.micros());
+        self.align_day_to_sun();
+
+        self.log(Event::ConfigChange);
+
+        ufmt::uwriteln!(&mut self.serial, "Config saved\r").unwrap();
+    }
+
+    /// Day start (init) or update of the modules run by the scheduler, in order
+    fn run_tasks(&mut self, t_us: u64, init: bool) {
+        let mut context = TaskContext {
+            t_us,
+            time_of_day_us: self.day_timer.get_elapsed_us(t_us).unwrap_or(0),
+            day_us: self.day_timer.get_timeout_us(),
+            wall_s: self.clock.is_set().then(|| self.clock.now_s(t_us)),
+            photoperiod: self.light.get_photoperiod(),
+            serial: &mut self.serial,
+            eeprom: &mut self.eeprom,
+            adc: &mut self.adc,
+            i2c: &mut self.i2c,
+        };
+
+        let mut tasks: [&mut dyn Task; 10] = [
+            &mut self.alive,
+            &mut self.light,
+            &mut self.filter,
+            &mut self.wavemaker,
+            &mut self.co2,
+            &mut self.air_pump,
+            &mut self.dosing_pump,
+            &mut self.top_off,
+            &mut self.ph_probe,
+            &mut self.alert,
+        ];
+
+        if init {
+            self.scheduler.init(&mut tasks, &mut context);
+        } else {
+            self.scheduler
+                .run(&mut tasks, &mut context, &self.sys_timer);
+        }
+    }
+
+    /// Following the sun, the days start at sunrise. Returns the length of the day starting
+    /// now and its light duration (s). A day started at another time (boot, clock set) ends
+    /// at the next sunrise, its light covers what is left of the daylight.
+    fn sun_day(&self, t_us: u64) -> Option<(u64, u32)> {
+        if !self.config.light.follow_sun || !self.clock.is_set() {
+            return None;
+        }
+
+        let now_s = self.clock.now_s(t_us);
+        let days = now_s / 86_400;
+        let second_of_day = now_s % 86_400;
+
+        let today = Daylight::compute(days, &self.config.light);
+        let tomorrow = Daylight::compute(days + 1, &self.config.light);
+
+        if second_of_day + Self::SUNRISE_TOLERANCE_S < today.sunrise_s {
+            // Night: dark until the sunrise
+            Some(((today.sunrise_s - second_of_day) as u64 * 1_000_000, 0))
+        } else {
+            Some((
+                (86_400 - second_of_day + tomorrow.sunrise_s) as u64 * 1_000_000,
+                today.sunset_s.saturating_sub(second_of_day),
+            ))
+        }
+    }
+
+    /// The current day ends at the next sunrise (e.g. once the clock is set)
+    fn align_day_to_sun(&mut self) {
+        let t_us = self.sys_timer.micros();
+
+        if let Some((day_us, _)) = self.sun_day(t_us) {
+            let elapsed_us = self.day_timer.get_elapsed_us(t_us).unwrap_or(0);
+
+            self.day_timer = Timer::new(elapsed_us + day_us);
+            self.day_timer.start(t_us - elapsed_us);
+        }
+    }
+
+    fn report_status(&mut self) {
+        self.clock.report(self.sys_timer.micros(), &mut self.serial);
+
+        if self.light.is_on() {
+            ufmt::uwriteln!(&mut self.serial, "Light: day\r").unwrap();
+        } else {
+            ufmt::uwriteln!(&mut self.serial, "Light: night\r").unwrap();
+        }
+
+        self.acclimation
+            .report(&self.config.acclimation, &mut self.serial);
+        self.light
+            .lamp_meter()
+            .report(self.config.light.lamp_life_h, &mut self.serial);
+    }
+
+    fn report_sun(&mut self) {
+        if !self.clock.is_set() {
+            ufmt::uwriteln!(&mut self.serial, "Time not set\r").unwrap();
+            return;
+        }
+
+        let now_s = self.clock.now_s(self.sys_timer.micros());
+        let daylight = Daylight::compute(now_s / 86_400, &self.config.light);
+
+        ufmt::uwrite!(&mut self.serial, "Sunrise ").unwrap();
+        write_time_of_day(&mut self.serial, daylight.sunrise_s);
+        ufmt::uwrite!(&mut self.serial, ", sunset ").unwrap();
+        write_time_of_day(&mut self.serial, daylight.sunset_s);
+
+        if self.config.light.follow_sun {
+            ufmt::uwriteln!(&mut self.serial, " (followed)\r").unwrap();
+        } else {
+            ufmt::uwriteln!(&mut self.serial, " (not followed: set light_sun 1)\r").unwrap();
+        }
+
+        let moon = Moon::compute(now_s, &self.config.light);
+        ufmt::uwrite!(
+            &mut self.serial,
+            "Moon {}% lit, rises ",
+            (moon.illumination() * 100.0) as u8
+        )
+        .unwrap();
+        write_time_of_day(&mut self.serial, moon.moonrise_s);
+        ufmt::uwrite!(&mut self.serial, ", sets ").unwrap();
+        write_time_of_day(&mut self.serial, moon.moonset_s);
+        ufmt::uwriteln!(&mut self.serial, "\r").unwrap();
+    }
+
+    /// Valid 7-bit addresses (0x00..0x07 and 0x78..0x7F are reserved)
+    fn report_i2c_scan(&mut self) {
+        const HEX_DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+
+        ufmt::uwrite!(&mut self.serial, "I2C:").unwrap();
+        let mut found = false;
+        for address in 0x08..=0x77 {
+            if self.i2c.probe(address) {
+                found = true;
+                ufmt::uwrite!(
+                    &mut self.serial,
+                    " 0x{}{}",
+                    HEX_DIGITS[(address >> 4) as usize] as char,
+                    HEX_DIGITS[(address & 0x0F) as usize] as char
+                )
+                .unwrap();
+            }
+        }
+        if !found {
+            ufmt::uwrite!(&mut self.serial, " no device").unwrap();
+        }
+
+        ufmt::uwriteln!(
+            &mut self.serial,
+            " (errors {}, bus recoveries {})\r",
+            self.i2c.error_count(),
+            self.i2c.recovery_count()
+        )
+        .unwrap();
+    }
+
+    /// Food is delivered once the filter has stopped
+    fn request_feeding(&mut self, t_us: u64, portions: u8) {
+        if self.pending_portions == 0 {
+            self.filter.pause_for_feeding(t_us);
+        }
+        self.pending_portions = self
+            .pending_portions
+            .saturating_add(portions)
+            .min(ScheduleAction::MAX_PORTIONS);
+    }
+
+    /// The weekly schedule follows the wall clock: nothing runs until the clock is set
+    fn run_schedule(&mut self, t_us: u64) {
+        if !self.clock.is_set() {
+            return;
+        }
+
+        let now_s = self.clock.now_s(t_us);
+        match self
+            .schedule
+            .update(now_s, &mut self.serial, &mut self.eeprom)
+        {
+            Some(ScheduleAction::Feed { portions }) => self.request_feeding(t_us, portions),
+            Some(ScheduleAction::Dose { ml }) => {
+                self.dosing_pump.dose_extra(t_us, ml, &mut self.serial)
+            }
+            Some(ScheduleAction::Light { hours }) => {
+                self.light_override_s = Some((hours as u32) * 3_600)
+            }
+            None => {}
+        }
+    }
+
+    fn update_alarms(&mut self, t_us: u64) {
+        let top_off_state = self.top_off.get_state();
+
+        self.set_alarm(AlarmId::ClockNotSet, !self.clock.is_set());
+        self.set_alarm(AlarmId::PhSensor, self.ph_probe.is_failed());
+        self.set_alarm(
+            AlarmId::TopOffTimeout,
+            top_off_state == TopOffState::LockedOut(LockoutCause::PumpTimeout),
+        );
+        self.set_alarm(
+            AlarmId::TopOffHighLevel,
+            top_off_state == TopOffState::LockedOut(LockoutCause::HighLevel),
+        );
+        self.set_alarm(AlarmId::DosingEmpty, self.dosing_pump.is_container_empty());
+        let lamp_life_h = self.config.light.lamp_life_h as u32;
+        self.set_alarm(
+            AlarmId::LampReplace,
+            lamp_life_h > 0 && self.light.lamp_meter().hours() >= lamp_life_h,
+        );
+        self.set_alarm(AlarmId::LampFailure, self.light.is_lamp_failed());
+        self.set_alarm(AlarmId::LightSensor, self.light.is_light_sensor_failed());
+
+        for pattern in BeatPattern::FAULTS {
+            self.alive
+                .set_pattern(pattern, self.alarms.is_pattern_shown(pattern));
+        }
+
+        self.co2
+            .set_inhibited(self.alarms.is_action_requested(AlarmAction::StopCo2));
+        self.dosing_pump
+            .set_inhibited(self.alarms.is_action_requested(AlarmAction::StopDosing));
+
+        // Muted or quiet hours: only the critical alarms sound
+        let hour = self
+            .clock
+            .is_set()
+            .then(|| DateTime::from_epoch_s(self.clock.now_s(t_us)).hour);
+        let is_quiet = self.config.buzzer.muted || self.config.buzzer.is_quiet(hour);
+
+        let melody = match self.alarms.sounding_severity() {
+            Some(Severity::Critical) => Some(CRITICAL_MELODY),
+            Some(_) if !is_quiet => Some(WARNING_MELODY),
+            _ => None,
+        };
+        self.alert.play(melody, t_us);
+    }
+
+    /// The status is gathered once per refresh, a few characters are sent per loop
+    fn update_display(&mut self, t_us: u64) {
+        if self.display.is_refresh_due(t_us) {
+            let now_s = self.clock.is_set().then(|| self.clock.now_s(t_us));
+
+            let mut alarm_names = [""; DISPLAY_ROWS];
+            for (name, id) in alarm_names.iter_mut().zip(self.alarms.shown()) {
+                *name = id.name();
+            }
+
+            let status = DisplayStatus {
+                now_s,
+                // No water temperature sensor yet
+                water_temp_dc: None,
+                light_on: self.light.is_on(),
+                light_pct: self.light.level_pct(),
+                next_feeding_s: now_s.and_then(|now_s| self.next_feeding_s(t_us, now_s)),
+                // No food level sensor yet
+                food_left_pct: None,
+                alarm_count: self.alarms.shown().count() as u8,
+                alarm_names,
+            };
+            self.display.refresh(t_us, &status, &mut self.i2c);
+        }
+
+        self.display.update(&mut self.i2c);
+    }
+
+    /// Scheduled feedings, or the daily feeding at the next day start
+    fn next_feeding_s(&self, t_us: u64, now_s: u32) -> Option<u32> {
+        if self.schedule.has_feedings() {
+            return self.schedule.next_feeding_s(now_s);
+        }
+
+        let day_end_us = self.day_timer.get_expiry_us()?;
+
+        Some(now_s + (day_end_us.saturating_sub(t_us) / 1_000_000) as u32)
+    }
+
+    fn set_alarm(&mut self, id: AlarmId, active: bool) {
+        if self.alarms.set(id, active, &mut self.serial) {
+            self.log(Event::Alarm { code: id as u8 });
+        }
+    }
+
+    fn log(&mut self, event: Event) {
+        self.event_log.record(
+            &mut self.eeprom,
+            event,
+            &self.clock,
+            self.sys_timer.micros(),
+        );
+    }
+
     #[allow(dead_code)]
     /// Example: self.usb_debug(format_args!("T_{}", 1));
     pub fn usb_debug(&mut self, args: Arguments) {
@@ -0,0 +1,51 @@
+use super::{config::LightConfig, sun::Daylight};
+use micromath::F32Ext;
+
+/// Moon seen from the configured place, at a wall time (s since 2000-01-01)
+pub struct Moon {
+    /// 0: new moon, 0.5: full moon
+    pub phase: f32,
+    /// Moonrise and moonset (s since the local midnight)
+    pub moonrise_s: u32,
+    pub moonset_s: u32,
+}
+
+impl Moon {
+    const SYNODIC_MONTH_DAYS: f32 = 29.530_588;
+    // First new moon of 2000: 2000-01-06 18:14 UTC
+    const NEW_MOON_DAYS: f32 = 5.76;
+
+    /// Mean phase (within ~1 day). The moon rises ~50min later each day: with the sun at
+    /// new moon, at sunset at full moon. Moonrise and moonset follow the sun times shifted
+    /// by the phase (a few hours error, enough for a night light)
+    pub fn compute(now_s: u32, config: &LightConfig) -> Self {
+        let days = now_s / 86_400;
+
+        // In f32, the seconds of 20+ years lose the fraction of day: split
+        let day = days as f32 + (now_s % 86_400) as f32 / 86_400.0;
+        let phase = ((day - Self::NEW_MOON_DAYS) / Self::SYNODIC_MONTH_DAYS).fract();
+
+        let daylight = Daylight::compute(days, config);
+        let lag_s = (phase * 86_400.0) as u32;
+
+        Self {
+            phase,
+            moonrise_s: (daylight.sunrise_s + lag_s) % 86_400,
+            moonset_s: (daylight.sunset_s + lag_s) % 86_400,
+        }
+    }
+
+    /// Lit fraction of the disc, 0..1
+    pub fn illumination(&self) -> f32 {
+        (1.0 - (2.0 * core::f32::consts::PI * self.phase).cos()) / 2.0
+    }
+
+    pub fn is_up(&self, second_of_day: u32) -> bool {
+        if self.moonrise_s <= self.moonset_s {
+            (self.moonrise_s..self.moonset_s).contains(&second_of_day)
+        } else {
+            // Across midnight
+            second_of_day >= self.moonrise_s || second_of_day < self.moonset_s
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use super::{
+    storage::{read_record, write_record, PANIC_RECORD_ADDR},
+    Serial,
+};
+use arduino_hal::{
+    hal::wdt::{Timeout, Wdt},
+    Eeprom,
+};
+use core::panic::PanicInfo;
+
+/// Location of the last panic, saved in EEPROM by the panic handler and reported at the next boot
+pub struct PanicRecord {
+    line: u16,
+    file: [u8; Self::FILE_LEN],
+    file_len: u8,
+}
+
+impl PanicRecord {
+    // Only the end of the path is kept: the file name
+    const FILE_LEN: usize = 24;
+    // [marker, line (u16 LE), file length, file..]
+    const RECORD_LEN: usize = 1 + 2 + 1 + Self::FILE_LEN;
+    // Erased once reported
+    const MARKER: u8 = 0xA5;
+
+    const BLINK_COUNT: u8 = 8;
+    const BLINK_MS: u16 = 100;
+    const BLINK_PAUSE_MS: u16 = 1_000;
+    const BLINK_REPEAT: u8 = 5;
+
+    fn new(file: &str, line: u32) -> Self {
+        let file = file.as_bytes();
+        let file = &file[file.len().saturating_sub(Self::FILE_LEN)..];
+
+        let mut record = Self {
+            line: line.min(u16::MAX as u32) as u16,
+            file: [0; Self::FILE_LEN],
+            file_len: file.len() as u8,
+        };
+        record.file[..file.len()].copy_from_slice(file);
+
+        record
+    }
+
+    fn save(&self, eeprom: &mut Eeprom) {
+        let mut record = [0; Self::RECORD_LEN];
+        record[0] = Self::MARKER;
+        record[1..3].copy_from_slice(&self.line.to_le_bytes());
+        record[3] = self.file_len;
+        record[4..].copy_from_slice(&self.file);
+
+        write_record(eeprom, PANIC_RECORD_ADDR, &record);
+    }
+
+    /// The record of the last panic, if any. It is cleared: reported once
+    pub fn take(eeprom: &mut Eeprom) -> Option<Self> {
+        let mut record = [0; Self::RECORD_LEN];
+        if !read_record(eeprom, PANIC_RECORD_ADDR, &mut record)
+            || record[0] != Self::MARKER
+            || record[3] as usize > Self::FILE_LEN
+        {
+            return None;
+        }
+
+        eeprom.erase_byte(PANIC_RECORD_ADDR);
+
+        let mut file = [0; Self::FILE_LEN];
+        file.copy_from_slice(&record[4..]);
+
+        Some(Self {
+            line: u16::from_le_bytes([record[1], record[2]]),
+            file,
+            file_len: record[3],
+        })
+    }
+
+    pub fn report(&self, serial: &mut Serial) {
+        let file = core::str::from_utf8(&self.file[..self.file_len as usize]).unwrap_or("?");
+
+        ufmt::uwriteln!(
+            serial,
+            "Last reset by a panic at ...{}:{}\r",
+            file,
+            self.line
+        )
+        .unwrap();
+    }
+}
+
+/// Replaces panic_halt, which froze the tank in its current state (e.g. light on):
+/// outputs off, panic location saved, fault code blinked, then reset by the watchdog.
+/// Nothing here may panic again.
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    avr_device::interrupt::disable();
+
+    // The application is dead: its peripherals are taken back
+    let dp = unsafe { arduino_hal::Peripherals::steal() };
+
+    // Safe state: PWM outputs disconnected, every output low (light, stepper, pumps,
+    // CO2, and the filter relay back to its normally closed state). No heater is wired yet.
+    dp.TC0.tccr0a.reset();
+    dp.TC1.tccr1a.reset();
+    dp.TC2.tccr2a.reset();
+    dp.PORTB.portb.reset();
+    dp.PORTC.portc.reset();
+    dp.PORTD.portd.reset();
+
+    if let Some(location) = info.location() {
+        let mut eeprom = Eeprom::new(dp.EEPROM);
+        PanicRecord::new(location.file(), location.line()).save(&mut eeprom);
+    }
+
+    let pins = arduino_hal::pins!(dp);
+    let mut led = pins.d13.into_output();
+
+    let mut watchdog = Wdt::new(dp.WDT, &dp.CPU.mcusr);
+    let _ = watchdog.start(Timeout::Ms8000);
+
+    // Fault code: bursts of fast blinks
+    for _ in 0..PanicRecord::BLINK_REPEAT {
+        for _ in 0..PanicRecord::BLINK_COUNT {
+            led.set_high();
+            arduino_hal::delay_ms(PanicRecord::BLINK_MS);
+            led.set_low();
+            arduino_hal::delay_ms(PanicRecord::BLINK_MS);
+        }
+        arduino_hal::delay_ms(PanicRecord::BLINK_PAUSE_MS);
+
+        watchdog.feed();
+    }
+
+    let _ = watchdog.start(Timeout::Ms16);
+
+    loop {
+        avr_device::asm::nop();
+    }
+}
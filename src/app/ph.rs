@@ -0,0 +1,328 @@
+use super::{
+    console::write_fixed_x100,
+    storage::{read_record, write_record, PH_CALIBRATION_ADDR},
+    task::{Task, TaskContext},
+    Serial,
+};
+use crate::drivers::{
+    adc::{AdcRequest, Channel, MedianFilter, Reference},
+    time::timer::Timer,
+};
+use arduino_hal::Eeprom;
+use micromath::F32Ext;
+
+#[derive(Clone, Copy, Default)]
+struct CalibrationPoint {
+    mv: u16,
+    ph_x100: u16,
+}
+
+/// Up to 3 points: points[0] is the neutral (pH 7) anchor, the others give the slope on each side
+struct Calibration {
+    points: [CalibrationPoint; 3],
+    count: u8,
+}
+
+impl Calibration {
+    const RECORD_LEN: usize = 3 * 4 + 1;
+
+    // Typical probe amplifier: pH 7 => 2.5V, Nernst slope -59.16 mV/pH at 25°C
+    const DEFAULT_NEUTRAL_MV: u16 = 2_500;
+    const DEFAULT_ACID_MV: u16 = 2_677;
+
+    // Smallest voltage step between the anchor and a buffer point, and the accepted slope
+    // magnitude (Nernst: 59.16 mV/pH): anything outside is a wrong buffer or a dead probe
+    const MIN_POINT_DELTA_MV: u16 = 100;
+    const MIN_SLOPE_MV_PER_PH: f32 = 45.0;
+    const MAX_SLOPE_MV_PER_PH: f32 = 70.0;
+
+    const REFERENCE_TEMP_C: f32 = 25.0;
+    const ZERO_CELSIUS_K: f32 = 273.15;
+
+    fn default() -> Self {
+        let mut points = [CalibrationPoint::default(); 3];
+        points[0] = CalibrationPoint {
+            mv: Self::DEFAULT_NEUTRAL_MV,
+            ph_x100: 700,
+        };
+        points[1] = CalibrationPoint {
+            mv: Self::DEFAULT_ACID_MV,
+            ph_x100: 400,
+        };
+
+        Self { points, count: 2 }
+    }
+
+    fn load(eeprom: &Eeprom) -> Option<Self> {
+        let mut record = [0; Self::RECORD_LEN];
+
+        if !read_record(eeprom, PH_CALIBRATION_ADDR, &mut record) {
+            return None;
+        }
+
+        let count = record[Self::RECORD_LEN - 1];
+        if !(2..=3).contains(&count) {
+            return None;
+        }
+
+        let mut points = [CalibrationPoint::default(); 3];
+        for (point, bytes) in points.iter_mut().zip(record.chunks_exact(4)) {
+            point.mv = u16::from_le_bytes([bytes[0], bytes[1]]);
+            point.ph_x100 = u16::from_le_bytes([bytes[2], bytes[3]]);
+        }
+
+        Some(Self { points, count })
+    }
+
+    fn save(&self, eeprom: &mut Eeprom) {
+        let mut record = [0; Self::RECORD_LEN];
+
+        for (point, bytes) in self.points.iter().zip(record.chunks_exact_mut(4)) {
+            bytes[..2].copy_from_slice(&point.mv.to_le_bytes());
+            bytes[2..].copy_from_slice(&point.ph_x100.to_le_bytes());
+        }
+        record[Self::RECORD_LEN - 1] = self.count;
+
+        write_record(eeprom, PH_CALIBRATION_ADDR, &record);
+    }
+
+    fn slope_mv_per_ph(anchor: &CalibrationPoint, point: &CalibrationPoint) -> f32 {
+        (point.mv as f32 - anchor.mv as f32) / (point.ph_x100 as f32 - anchor.ph_x100 as f32)
+            * 100.0
+    }
+
+    fn is_plausible(&self) -> bool {
+        let anchor = &self.points[0];
+        let first_slope = Self::slope_mv_per_ph(anchor, &self.points[1]);
+
+        // Both sides of a 3-point calibration must agree on the slope sign
+        self.points[1..self.count as usize].iter().all(|point| {
+            let slope = Self::slope_mv_per_ph(anchor, point);
+
+            anchor.mv.abs_diff(point.mv) >= Self::MIN_POINT_DELTA_MV
+                && (Self::MIN_SLOPE_MV_PER_PH..=Self::MAX_SLOPE_MV_PER_PH).contains(&slope.abs())
+                && (slope < 0.0) == (first_slope < 0.0)
+        })
+    }
+
+    fn ph(&self, mv: f32, water_temp_c: Option<f32>) -> f32 {
+        let anchor = &self.points[0];
+        let anchor_ph = anchor.ph_x100 as f32 / 100.0;
+
+        // Pick the segment on the side of the reading (3-point calibration)
+        let mut slope = Self::slope_mv_per_ph(anchor, &self.points[1]);
+        if self.count == 3 {
+            let first_side = self.points[1].ph_x100 > anchor.ph_x100;
+            let reading_side = anchor_ph + (mv - anchor.mv as f32) / slope > anchor_ph;
+
+            if first_side != reading_side {
+                slope = Self::slope_mv_per_ph(anchor, &self.points[2]);
+            }
+        }
+
+        // Nernst: the slope is proportional to the absolute temperature
+        if let Some(temp_c) = water_temp_c {
+            slope *=
+                (temp_c + Self::ZERO_CELSIUS_K) / (Self::REFERENCE_TEMP_C + Self::ZERO_CELSIUS_K);
+        }
+
+        anchor_ph + (mv - anchor.mv as f32) / slope
+    }
+}
+
+enum CalibrationState {
+    Idle,
+    WaitingBuffer { index: usize },
+}
+
+/// pH probe amplifier on A6 (ADC6)
+pub struct PhProbe {
+    calibration: Calibration,
+    calibration_state: CalibrationState,
+    sample_timer: Timer,
+    report_timer: Timer,
+    sampling: bool,
+    // Median of the 5 last readings
+    median_mv: MedianFilter<5>,
+}
+
+impl PhProbe {
+    const SAMPLE_PERIOD_US: u64 = 1_000_000; // 1s
+    const REPORT_PERIOD_US: u64 = 60 * 1_000 * 1_000; // 1min
+
+    // 16 samples => 12 bits
+    const ADC_REQUEST: AdcRequest = AdcRequest::new(Channel::Adc6, Reference::Avcc, 2);
+
+    // Spread of the median window under which the reading is considered stable (calibration)
+    const STABLE_SPREAD_MV: u16 = 3;
+
+    // Amplifier output stuck near a rail (AVcc = 5V): probe disconnected or amplifier fault
+    const RAIL_LOW_MV: u16 = 100;
+    const RAIL_HIGH_MV: u16 = 4_900;
+
+    // Calibration buffer solutions, in the order they are requested
+    const BUFFERS_X100: [u16; 3] = [700, 400, 1_000];
+
+    pub fn new(eeprom: &Eeprom) -> Self {
+        Self {
+            calibration: Calibration::load(eeprom).unwrap_or_else(Calibration::default),
+            calibration_state: CalibrationState::Idle,
+            sample_timer: Timer::new(Self::SAMPLE_PERIOD_US),
+            report_timer: Timer::new(Self::REPORT_PERIOD_US),
+            sampling: false,
+            median_mv: MedianFilter::new(),
+        }
+    }
+
+    pub fn get_ph(&self, water_temp_c: Option<f32>) -> Option<f32> {
+        self.median_mv
+            .median()
+            .map(|mv| self.calibration.ph(mv as f32, water_temp_c))
+    }
+
+    pub fn is_failed(&self) -> bool {
+        matches!(
+            self.median_mv.median(),
+            Some(mv) if mv < Self::RAIL_LOW_MV || mv > Self::RAIL_HIGH_MV
+        )
+    }
+
+    pub fn report(&self, serial: &mut Serial, water_temp_c: Option<f32>) {
+        match self.get_ph(water_temp_c) {
+            Some(ph) => {
+                ufmt::uwrite!(serial, "pH: ").unwrap();
+                write_fixed_x100(serial, (ph * 100.0) as i32);
+                ufmt::uwriteln!(serial, "\r").unwrap();
+            }
+            None => ufmt::uwriteln!(serial, "pH: no reading yet\r").unwrap(),
+        }
+    }
+
+    pub fn start_calibration(&mut self, serial: &mut Serial) {
+        self.calibration_state = CalibrationState::WaitingBuffer { index: 0 };
+
+        Self::prompt_buffer(serial, 0);
+    }
+
+    /// "ok": the probe is in the requested buffer solution
+    pub fn calibration_next(&mut self, serial: &mut Serial, eeprom: &mut Eeprom) {
+        if let CalibrationState::WaitingBuffer { index } = self.calibration_state {
+            let mv = match (self.median_mv.median(), self.median_mv.spread()) {
+                (Some(mv), Some(spread)) if spread <= Self::STABLE_SPREAD_MV => mv,
+                _ => {
+                    ufmt::uwriteln!(serial, "Reading not stable yet, send 'ok' again\r").unwrap();
+                    return;
+                }
+            };
+
+            self.calibration.points[index] = CalibrationPoint {
+                mv,
+                ph_x100: Self::BUFFERS_X100[index],
+            };
+
+            let next_index = index + 1;
+            if next_index == Self::BUFFERS_X100.len() {
+                self.calibration.count = next_index as u8;
+                self.finish_calibration(serial, eeprom);
+            } else {
+                self.calibration_state = CalibrationState::WaitingBuffer { index: next_index };
+
+                Self::prompt_buffer(serial, next_index);
+                if next_index == Self::BUFFERS_X100.len() - 1 {
+                    ufmt::uwriteln!(serial, "or send 'done' for a 2-point calibration\r").unwrap();
+                }
+            }
+        }
+    }
+
+    /// "done": stop after the 2 first points
+    pub fn calibration_done(&mut self, serial: &mut Serial, eeprom: &mut Eeprom) {
+        if let CalibrationState::WaitingBuffer { index } = self.calibration_state {
+            if index >= 2 {
+                self.calibration.count = index as u8;
+                self.finish_calibration(serial, eeprom);
+            } else {
+                ufmt::uwriteln!(serial, "At least 2 points are required\r").unwrap();
+            }
+        }
+    }
+
+    pub fn calibration_abort(&mut self, serial: &mut Serial, eeprom: &Eeprom) {
+        if let CalibrationState::WaitingBuffer { .. } = self.calibration_state {
+            self.calibration_state = CalibrationState::Idle;
+
+            // Drop the partially captured points
+            self.calibration = Calibration::load(eeprom).unwrap_or_else(Calibration::default);
+
+            ufmt::uwriteln!(serial, "pH calibration aborted\r").unwrap();
+        }
+    }
+
+    fn finish_calibration(&mut self, serial: &mut Serial, eeprom: &mut Eeprom) {
+        self.calibration_state = CalibrationState::Idle;
+
+        if !self.calibration.is_plausible() {
+            // Keep the previous calibration
+            self.calibration = Calibration::load(eeprom).unwrap_or_else(Calibration::default);
+
+            ufmt::uwriteln!(
+                serial,
+                "pH calibration rejected: implausible slope, previous calibration kept\r"
+            )
+            .unwrap();
+            return;
+        }
+
+        self.calibration.save(eeprom);
+
+        ufmt::uwriteln!(
+            serial,
+            "pH calibration saved ({} points)\r",
+            self.calibration.count
+        )
+        .unwrap();
+    }
+
+    fn prompt_buffer(serial: &mut Serial, index: usize) {
+        ufmt::uwrite!(serial, "Rinse the probe, put it in the pH ").unwrap();
+        write_fixed_x100(serial, Self::BUFFERS_X100[index] as i32);
+        ufmt::uwriteln!(serial, " buffer, wait for a stable reading and send 'ok'\r").unwrap();
+    }
+}
+
+impl Task for PhProbe {
+    fn name(&self) -> &'static str {
+        "pH"
+    }
+
+    fn init(&mut self, context: &mut TaskContext) {
+        self.sample_timer.start(context.t_us);
+        self.report_timer.start(context.t_us);
+    }
+
+    fn update(&mut self, context: &mut TaskContext) {
+        let t_us = context.t_us;
+
+        if let Ok(true) = self.sample_timer.has_expired(t_us) {
+            self.sampling = true;
+            self.sample_timer.start(t_us);
+        }
+
+        if self.sampling {
+            if let Some(raw) = context.adc.read(t_us, &Self::ADC_REQUEST) {
+                self.median_mv
+                    .push(Self::ADC_REQUEST.to_millivolts(raw) as u16);
+                self.sampling = false;
+            }
+        }
+
+        if let Ok(true) = self.report_timer.has_expired(t_us) {
+            // No periodic report while calibrating: it would hide the instructions
+            // No water temperature sensor yet: no pH temperature compensation
+            if let CalibrationState::Idle = self.calibration_state {
+                self.report(context.serial, None);
+            }
+            self.report_timer.start(t_us);
+        }
+    }
+}
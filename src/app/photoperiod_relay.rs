@@ -0,0 +1,91 @@
+use super::{
+    light::Photoperiod,
+    task::{Task, TaskContext},
+};
+use arduino_hal::port::{mode::Output, Pin, PinOps};
+
+pub enum LightEvent {
+    LightOn,
+    LightOff,
+}
+
+/// Time of day defined relative to a light transition, e.g. 1h before the light goes on
+pub struct RelativeTime {
+    event: LightEvent,
+    offset_s: i32,
+}
+
+impl RelativeTime {
+    pub const fn new(event: LightEvent, offset_s: i32) -> Self {
+        Self { event, offset_s }
+    }
+
+    fn time_of_day_us(&self, photoperiod: &Photoperiod, day_us: u64) -> u64 {
+        let event_us = match self.event {
+            LightEvent::LightOn => photoperiod.on_us,
+            LightEvent::LightOff => photoperiod.off_us,
+        } as i64;
+
+        (event_us + (self.offset_s as i64) * 1_000_000).rem_euclid(day_us as i64) as u64
+    }
+}
+
+/// Relay output following the light photoperiod: when the light schedule changes, it moves with it
+pub struct PhotoperiodRelay<RelayPin: PinOps> {
+    name: &'static str,
+    pin: Pin<Output, RelayPin>,
+    on: RelativeTime,
+    off: RelativeTime,
+    inhibited: bool,
+}
+
+impl<RelayPin: PinOps> PhotoperiodRelay<RelayPin> {
+    pub fn new(
+        name: &'static str,
+        mut relay_pin: Pin<Output, RelayPin>,
+        on: RelativeTime,
+        off: RelativeTime,
+    ) -> Self {
+        relay_pin.set_low();
+
+        Self {
+            name,
+            pin: relay_pin,
+            on,
+            off,
+            inhibited: false,
+        }
+    }
+
+    /// Forced off whatever the schedule (e.g. alarm safety action)
+    pub fn set_inhibited(&mut self, inhibited: bool) {
+        self.inhibited = inhibited;
+    }
+}
+
+impl<RelayPin: PinOps> Task for PhotoperiodRelay<RelayPin> {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn update(&mut self, context: &mut TaskContext) {
+        let time_of_day_us = context.time_of_day_us;
+        let on_us = self.on.time_of_day_us(&context.photoperiod, context.day_us);
+        let off_us = self
+            .off
+            .time_of_day_us(&context.photoperiod, context.day_us);
+
+        let is_on = if on_us <= off_us {
+            (on_us..off_us).contains(&time_of_day_us)
+        } else {
+            // Period across midnight (day start)
+            time_of_day_us >= on_us || time_of_day_us < off_us
+        };
+
+        if is_on && !self.inhibited {
+            self.pin.set_high();
+        } else {
+            self.pin.set_low();
+        }
+    }
+}
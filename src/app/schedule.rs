@@ -0,0 +1,342 @@
+use super::{
+    clock::{weekday, write_time_of_day},
+    storage::{read_record, write_record, SCHEDULE_ADDR},
+    Serial,
+};
+use arduino_hal::Eeprom;
+
+/// Set of weekdays, bit 0: Monday .. bit 6: Sunday
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Weekdays(u8);
+
+impl Weekdays {
+    const LETTERS: &'static [u8; 7] = b"MTWTFSS";
+
+    /// "MTWTF..": one character per day from Monday, '.' for the days off
+    pub fn parse(text: &[u8]) -> Option<Self> {
+        if text.len() != 7 {
+            return None;
+        }
+
+        let mut bits = 0;
+        for (day, (character, letter)) in text.iter().zip(Self::LETTERS).enumerate() {
+            match *character {
+                b'.' => {}
+                character if character.to_ascii_uppercase() == *letter => bits |= 1 << day,
+                _ => return None,
+            }
+        }
+
+        (bits != 0).then(|| Self(bits))
+    }
+
+    fn contains(&self, weekday: u8) -> bool {
+        self.0 & (1 << weekday) != 0
+    }
+
+    fn write(&self, serial: &mut Serial) {
+        for (day, letter) in Self::LETTERS.iter().enumerate() {
+            let character = if self.contains(day as u8) {
+                *letter
+            } else {
+                b'.'
+            };
+            ufmt::uwrite!(serial, "{}", character as char).unwrap();
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ScheduleAction {
+    /// At most MAX_PORTIONS: the filter stays paused while they are delivered
+    Feed { portions: u8 },
+    /// Extra dose, on top of the daily program (same safety limits)
+    Dose { ml: u8 },
+    /// Photoperiod of the next day (e.g. shorter on some weekdays), within the day length.
+    /// The acclimation ramp has priority
+    Light { hours: u8 },
+}
+
+impl ScheduleAction {
+    pub const MAX_PORTIONS: u8 = 5;
+    pub const MAX_LIGHT_HOURS: u8 = 24;
+
+    fn code(&self) -> (u8, u8) {
+        match *self {
+            ScheduleAction::Feed { portions } => (0, portions),
+            ScheduleAction::Dose { ml } => (1, ml),
+            ScheduleAction::Light { hours } => (2, hours),
+        }
+    }
+
+    fn from_code(code: u8, parameter: u8) -> Option<Self> {
+        match code {
+            0 => Some(ScheduleAction::Feed {
+                portions: parameter,
+            }),
+            1 => Some(ScheduleAction::Dose { ml: parameter }),
+            2 => Some(ScheduleAction::Light { hours: parameter }),
+            _ => None,
+        }
+    }
+
+    fn write(&self, serial: &mut Serial) {
+        match *self {
+            ScheduleAction::Feed { portions } => {
+                ufmt::uwrite!(serial, "feed {} portion(s)", portions).unwrap()
+            }
+            ScheduleAction::Dose { ml } => ufmt::uwrite!(serial, "dose {} ml", ml).unwrap(),
+            ScheduleAction::Light { hours } => {
+                ufmt::uwrite!(serial, "light {}h next day", hours).unwrap()
+            }
+        }
+    }
+}
+
+/// What to do with an event missed while the power was off (or the clock not set)
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CatchUp {
+    /// Run once, late
+    RunLate,
+    Skip,
+}
+
+#[derive(Clone, Copy)]
+pub struct ScheduleRule {
+    pub weekdays: Weekdays,
+    pub minute_of_day: u16,
+    pub action: ScheduleAction,
+    pub catch_up: CatchUp,
+}
+
+#[derive(Clone, Copy)]
+struct Slot {
+    rule: ScheduleRule,
+    // Wall time (s since 2000-01-01) of the last handled occurrence, run or skipped
+    last_occurrence_s: u32,
+}
+
+/// Weekly program evaluated against the wall clock. Each rule keeps its last handled
+/// occurrence in EEPROM: the events missed during a power loss are found when the clock
+/// is set again.
+pub struct Schedule {
+    slots: [Option<Slot>; Self::MAX_RULES],
+    last_update_s: Option<u32>,
+}
+
+impl Schedule {
+    pub const MAX_RULES: usize = 8;
+
+    const RECORD_LEN: usize = 10;
+    // Record + CRC8
+    const SLOT_STRIDE: u16 = 12;
+
+    // Events run up to 1min after their time are on time (blocking feeding, loop jitter)
+    const ON_TIME_TOLERANCE_S: u32 = 60;
+
+    pub fn new(eeprom: &Eeprom) -> Self {
+        let mut slots = [None; Self::MAX_RULES];
+
+        for (index, slot) in slots.iter_mut().enumerate() {
+            let mut record = [0; Self::RECORD_LEN];
+            if read_record(eeprom, Self::slot_addr(index), &mut record) {
+                *slot = Self::decode(&record);
+            }
+        }
+
+        Self {
+            slots,
+            last_update_s: None,
+        }
+    }
+
+    /// Returns false if all the slots are used.
+    /// The occurrences before now are considered handled: the rule only runs from now on
+    pub fn add(&mut self, rule: ScheduleRule, now_s: u32, eeprom: &mut Eeprom) -> bool {
+        let index = match self.slots.iter().position(|slot| slot.is_none()) {
+            Some(index) => index,
+            None => return false,
+        };
+
+        let slot = Slot {
+            rule,
+            last_occurrence_s: Self::last_occurrence_s(&rule, now_s).unwrap_or(0),
+        };
+        self.slots[index] = Some(slot);
+        Self::save(index, &slot, eeprom);
+
+        true
+    }
+
+    /// Returns false if there is no such rule
+    pub fn remove(&mut self, index: usize, eeprom: &mut Eeprom) -> bool {
+        if !matches!(self.slots.get(index), Some(Some(_))) {
+            return false;
+        }
+
+        self.slots[index] = None;
+        write_record(eeprom, Self::slot_addr(index), &[0; Self::RECORD_LEN]);
+
+        true
+    }
+
+    /// Once feedings are programmed, there is no more feeding at the day start
+    pub fn has_feedings(&self) -> bool {
+        self.slots
+            .iter()
+            .flatten()
+            .any(|slot| matches!(slot.rule.action, ScheduleAction::Feed { .. }))
+    }
+
+    /// Wall time of the next feeding (within the next week)
+    pub fn next_feeding_s(&self, now_s: u32) -> Option<u32> {
+        self.slots
+            .iter()
+            .flatten()
+            .filter(|slot| matches!(slot.rule.action, ScheduleAction::Feed { .. }))
+            .filter_map(|slot| Self::next_occurrence_s(&slot.rule, now_s))
+            .min()
+    }
+
+    /// To be called with the wall time once the clock is set.
+    /// Returns the next action to run, one at a time
+    pub fn update(
+        &mut self,
+        now_s: u32,
+        serial: &mut Serial,
+        eeprom: &mut Eeprom,
+    ) -> Option<ScheduleAction> {
+        // Evaluated once per second, until all due actions are returned
+        if self.last_update_s == Some(now_s) {
+            return None;
+        }
+
+        for (index, slot) in self.slots.iter_mut().enumerate() {
+            let slot = match slot {
+                Some(slot) => slot,
+                None => continue,
+            };
+
+            let occurrence_s = match Self::last_occurrence_s(&slot.rule, now_s) {
+                Some(occurrence_s) if occurrence_s > slot.last_occurrence_s => occurrence_s,
+                _ => continue,
+            };
+
+            slot.last_occurrence_s = occurrence_s;
+            Self::save(index, slot, eeprom);
+
+            let late_s = now_s - occurrence_s;
+            if late_s <= Self::ON_TIME_TOLERANCE_S {
+                return Some(slot.rule.action);
+            }
+
+            if slot.rule.catch_up == CatchUp::RunLate {
+                ufmt::uwriteln!(serial, "Schedule {}: missed, run {}s late\r", index, late_s)
+                    .unwrap();
+                return Some(slot.rule.action);
+            }
+
+            ufmt::uwriteln!(serial, "Schedule {}: missed, skipped\r", index).unwrap();
+        }
+
+        self.last_update_s = Some(now_s);
+
+        None
+    }
+
+    pub fn report(&self, serial: &mut Serial) {
+        let mut count = 0;
+
+        for (index, slot) in self.slots.iter().enumerate() {
+            let rule = match slot {
+                Some(slot) => slot.rule,
+                None => continue,
+            };
+
+            ufmt::uwrite!(serial, "{}: ", index).unwrap();
+            rule.weekdays.write(serial);
+            ufmt::uwrite!(serial, " ").unwrap();
+            write_time_of_day(serial, (rule.minute_of_day as u32) * 60);
+            ufmt::uwrite!(serial, " ").unwrap();
+            rule.action.write(serial);
+            match rule.catch_up {
+                CatchUp::RunLate => ufmt::uwriteln!(serial, ", run late if missed\r").unwrap(),
+                CatchUp::Skip => ufmt::uwriteln!(serial, ", skipped if missed\r").unwrap(),
+            }
+
+            count += 1;
+        }
+
+        if count == 0 {
+            ufmt::uwriteln!(serial, "No schedule\r").unwrap();
+        }
+    }
+
+    /// Latest occurrence at or before now (within the last week)
+    fn last_occurrence_s(rule: &ScheduleRule, now_s: u32) -> Option<u32> {
+        let today = now_s / 86_400;
+
+        (0..=7)
+            .filter(|days_ago| *days_ago <= today)
+            .find_map(|days_ago| {
+                let day = today - days_ago;
+                let occurrence_s = day * 86_400 + (rule.minute_of_day as u32) * 60;
+
+                (rule.weekdays.contains(weekday(day)) && occurrence_s <= now_s)
+                    .then(|| occurrence_s)
+            })
+    }
+
+    /// Earliest occurrence after now (within the next week)
+    fn next_occurrence_s(rule: &ScheduleRule, now_s: u32) -> Option<u32> {
+        let today = now_s / 86_400;
+
+        (0..=7).find_map(|days_ahead| {
+            let day = today + days_ahead;
+            let occurrence_s = day * 86_400 + (rule.minute_of_day as u32) * 60;
+
+            (rule.weekdays.contains(weekday(day)) && occurrence_s > now_s).then(|| occurrence_s)
+        })
+    }
+
+    fn slot_addr(index: usize) -> u16 {
+        SCHEDULE_ADDR + (index as u16) * Self::SLOT_STRIDE
+    }
+
+    /// [weekdays, minute of day LE, action, parameter, catch-up, last occurrence LE]
+    fn save(index: usize, slot: &Slot, eeprom: &mut Eeprom) {
+        let rule = &slot.rule;
+        let (action, parameter) = rule.action.code();
+
+        let mut record = [0; Self::RECORD_LEN];
+        record[0] = rule.weekdays.0;
+        record[1..3].copy_from_slice(&rule.minute_of_day.to_le_bytes());
+        record[3] = action;
+        record[4] = parameter;
+        record[5] = (rule.catch_up == CatchUp::RunLate) as u8;
+        record[6..].copy_from_slice(&slot.last_occurrence_s.to_le_bytes());
+
+        write_record(eeprom, Self::slot_addr(index), &record);
+    }
+
+    /// Cleared slot: no weekday
+    fn decode(record: &[u8; Self::RECORD_LEN]) -> Option<Slot> {
+        if record[0] == 0 {
+            return None;
+        }
+
+        Some(Slot {
+            rule: ScheduleRule {
+                weekdays: Weekdays(record[0]),
+                minute_of_day: u16::from_le_bytes([record[1], record[2]]),
+                action: ScheduleAction::from_code(record[3], record[4])?,
+                catch_up: if record[5] != 0 {
+                    CatchUp::RunLate
+                } else {
+                    CatchUp::Skip
+                },
+            },
+            last_occurrence_s: u32::from_le_bytes([record[6], record[7], record[8], record[9]]),
+        })
+    }
+}
@@ -0,0 +1,301 @@
+use super::{clock::write_time_of_day, config::DisplayConfig};
+use crate::drivers::{
+    display::{hd44780::Hd44780, ssd1306::Ssd1306, TextDisplay, COLUMNS, ROWS},
+    time::timer::Timer,
+    twi::Twi,
+};
+use core::convert::Infallible;
+use ufmt::uWrite;
+
+/// Gathered by the application at each refresh
+pub struct DisplayStatus {
+    /// Wall time (s since 2000-01-01), once the clock is set
+    pub now_s: Option<u32>,
+    pub water_temp_dc: Option<i16>, // (0.1 degC)
+    pub light_on: bool,
+    /// Output intensity (moonlight at night)
+    pub light_pct: u8,
+    /// Wall time
+    pub next_feeding_s: Option<u32>,
+    pub food_left_pct: Option<u8>,
+    pub alarm_count: u8,
+    /// First shown alarms
+    pub alarm_names: [&'static str; ROWS],
+}
+
+enum DisplayDriver {
+    Oled(Ssd1306),
+    Lcd(Hd44780),
+}
+
+impl DisplayDriver {
+    fn display(&mut self) -> &mut dyn TextDisplay {
+        match self {
+            DisplayDriver::Oled(oled) => oled,
+            DisplayDriver::Lcd(lcd) => lcd,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Page {
+    Summary,
+    Alarms,
+}
+
+/// Row being composed, padded with spaces (clears the previous text)
+struct Line {
+    text: [u8; COLUMNS],
+    len: usize,
+}
+
+impl Line {
+    fn new() -> Self {
+        Self {
+            text: [b' '; COLUMNS],
+            len: 0,
+        }
+    }
+}
+
+impl uWrite for Line {
+    type Error = Infallible;
+
+    /// Clipped at the end of the row
+    fn write_str(&mut self, s: &str) -> Result<(), Infallible> {
+        for byte in s.bytes() {
+            if self.len < COLUMNS {
+                self.text[self.len] = byte;
+                self.len += 1;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Status screen on an SSD1306 OLED or an HD44780 LCD, whichever answers on the I2C bus at
+/// boot. The rows are composed once per second into a copy of the screen: only the changed
+/// characters are sent, a few per loop (blocking I2C transfers).
+pub struct StatusDisplay {
+    driver: Option<DisplayDriver>,
+    config: DisplayConfig,
+    // Initialized: false again after a transfer error (display power loss)
+    ready: bool,
+    powered: bool,
+    shown: [[u8; COLUMNS]; ROWS],
+    // Columns still to send per row: [start, end)
+    dirty: [Option<(u8, u8)>; ROWS],
+    page: Page,
+    refresh_timer: Timer,
+    page_timer: Timer,
+}
+
+impl StatusDisplay {
+    const REFRESH_PERIOD_US: u64 = 1_000_000; // 1s
+
+    // A few ms on the bus per update
+    const FLUSH_MAX_CHARACTERS: u8 = 8;
+
+    /// The OLED first, then the LCD
+    pub fn new(config: &DisplayConfig, bus: &mut Twi) -> Self {
+        let driver = if bus.probe(Ssd1306::ADDRESS) {
+            Some(DisplayDriver::Oled(Ssd1306::new(Ssd1306::ADDRESS)))
+        } else if bus.probe(Hd44780::ADDRESS) {
+            Some(DisplayDriver::Lcd(Hd44780::new(Hd44780::ADDRESS)))
+        } else {
+            None
+        };
+
+        Self {
+            driver,
+            config: *config,
+            ready: false,
+            powered: false,
+            shown: [[b' '; COLUMNS]; ROWS],
+            dirty: [None; ROWS],
+            page: Page::Summary,
+            refresh_timer: Timer::new(Self::REFRESH_PERIOD_US),
+            page_timer: Timer::new(Self::page_us(config)),
+        }
+    }
+
+    pub fn set_config(&mut self, config: &DisplayConfig) {
+        self.config = *config;
+        self.page_timer = Timer::new(Self::page_us(config));
+    }
+
+    /// At each day start (system time reset)
+    pub fn init(&mut self, t_us: u64) {
+        self.refresh_timer.start(t_us);
+        self.page_timer.start(t_us);
+    }
+
+    /// The status is only gathered when needed
+    pub fn is_refresh_due(&self, t_us: u64) -> bool {
+        self.driver.is_some() && matches!(self.refresh_timer.has_expired(t_us), Ok(true))
+    }
+
+    pub fn refresh(&mut self, t_us: u64, status: &DisplayStatus, bus: &mut Twi) {
+        self.refresh_timer.start(t_us);
+
+        let driver = match &mut self.driver {
+            Some(driver) => driver,
+            None => return,
+        };
+
+        if !self.ready {
+            if driver.display().init(bus).is_err() {
+                return;
+            }
+            self.ready = true;
+            self.powered = true;
+            // Blank screen
+            self.shown = [[b' '; COLUMNS]; ROWS];
+            self.dirty = [None; ROWS];
+        }
+
+        // Night: off unless there is an alarm to show
+        let on = !(self.config.night_sleep && !status.light_on && status.alarm_count == 0);
+        if on != self.powered {
+            if driver.display().set_power(bus, on).is_err() {
+                self.ready = false;
+                return;
+            }
+            self.powered = on;
+        }
+        if !on {
+            return;
+        }
+
+        if status.alarm_count == 0 {
+            self.page = Page::Summary;
+        } else if self.config.page_s > 0 && matches!(self.page_timer.has_expired(t_us), Ok(true)) {
+            self.page = match self.page {
+                Page::Summary => Page::Alarms,
+                Page::Alarms => Page::Summary,
+            };
+            self.page_timer.start(t_us);
+        }
+
+        match self.page {
+            Page::Summary => self.compose_summary(status),
+            Page::Alarms => self.compose_alarms(status),
+        }
+    }
+
+    /// Sends a few of the changed characters
+    pub fn update(&mut self, bus: &mut Twi) {
+        if !self.ready || !self.powered {
+            return;
+        }
+
+        let (row, (start, end)) = match self
+            .dirty
+            .iter()
+            .enumerate()
+            .find_map(|(row, span)| span.map(|span| (row, span)))
+        {
+            Some(dirty) => dirty,
+            None => return,
+        };
+        let chunk_end = end.min(start + Self::FLUSH_MAX_CHARACTERS);
+
+        if let Some(driver) = &mut self.driver {
+            let text = &self.shown[row][start as usize..chunk_end as usize];
+
+            if driver
+                .display()
+                .write_text(bus, row, start as usize, text)
+                .is_ok()
+            {
+                self.dirty[row] = (chunk_end < end).then(|| (chunk_end, end));
+            } else {
+                // Initialized again at the next refresh
+                self.ready = false;
+            }
+        }
+    }
+
+    fn page_us(config: &DisplayConfig) -> u64 {
+        (config.page_s as u64) * 1_000_000
+    }
+
+    /// Time, light, water, feeding and food, alarm count
+    fn compose_summary(&mut self, status: &DisplayStatus) {
+        let mut line = Line::new();
+        match status.now_s {
+            Some(now_s) => write_time_of_day(&mut line, now_s),
+            None => ufmt::uwrite!(line, "--:--").unwrap(),
+        }
+        if status.light_on {
+            ufmt::uwrite!(line, "  Day {}%", status.light_pct).unwrap();
+        } else if status.light_pct > 0 {
+            ufmt::uwrite!(line, "  Moon {}%", status.light_pct).unwrap();
+        } else {
+            ufmt::uwrite!(line, "  Night").unwrap();
+        }
+        self.set_row(0, &line);
+
+        let mut line = Line::new();
+        match status.water_temp_dc {
+            Some(temp_dc) => {
+                let sign = if temp_dc < 0 { "-" } else { "" };
+                let temp_dc = temp_dc.unsigned_abs();
+                ufmt::uwrite!(line, "Water {}{}.{}C", sign, temp_dc / 10, temp_dc % 10).unwrap();
+            }
+            None => ufmt::uwrite!(line, "Water --.-C").unwrap(),
+        }
+        self.set_row(1, &line);
+
+        let mut line = Line::new();
+        ufmt::uwrite!(line, "Feed ").unwrap();
+        match status.next_feeding_s {
+            Some(feeding_s) => write_time_of_day(&mut line, feeding_s),
+            None => ufmt::uwrite!(line, "--:--").unwrap(),
+        }
+        match status.food_left_pct {
+            Some(food_pct) => ufmt::uwrite!(line, "  Food {}%", food_pct).unwrap(),
+            None => ufmt::uwrite!(line, "  Food --").unwrap(),
+        }
+        self.set_row(2, &line);
+
+        let mut line = Line::new();
+        match status.alarm_count {
+            0 => ufmt::uwrite!(line, "No alarm").unwrap(),
+            1 => ufmt::uwrite!(line, "1 alarm").unwrap(),
+            count => ufmt::uwrite!(line, "{} alarms", count).unwrap(),
+        }
+        self.set_row(3, &line);
+    }
+
+    /// An alarm name per row
+    fn compose_alarms(&mut self, status: &DisplayStatus) {
+        for (row, name) in status.alarm_names.iter().enumerate() {
+            let mut line = Line::new();
+            ufmt::uwrite!(line, "{}", *name).unwrap();
+            self.set_row(row, &line);
+        }
+    }
+
+    /// Only the span between the first and the last changed character is sent
+    fn set_row(&mut self, row: usize, line: &Line) {
+        let shown = &mut self.shown[row];
+
+        let first = shown.iter().zip(line.text.iter()).position(|(a, b)| a != b);
+        let last = shown
+            .iter()
+            .zip(line.text.iter())
+            .rposition(|(a, b)| a != b);
+
+        if let (Some(first), Some(last)) = (first, last) {
+            let (start, end) = (first as u8, last as u8 + 1);
+
+            self.dirty[row] = Some(match self.dirty[row] {
+                Some((dirty_start, dirty_end)) => (dirty_start.min(start), dirty_end.max(end)),
+                None => (start, end),
+            });
+            *shown = line.text;
+        }
+    }
+}
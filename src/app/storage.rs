@@ -0,0 +1,57 @@
+use arduino_hal::Eeprom;
+
+// EEPROM map (ATmega328P: 1024 bytes)
+pub const PH_CALIBRATION_ADDR: u16 = 0x0000; // 16 bytes
+pub const DOSING_PUMP_ADDR: u16 = 0x0010; // 16 bytes
+pub const CONFIG_ADDR: u16 = 0x0020; // 96 bytes
+pub const SCHEDULE_ADDR: u16 = 0x0080; // 96 bytes (8 rules)
+pub const ACCLIMATION_ADDR: u16 = 0x00E0; // 8 bytes
+pub const LAMP_METER_ADDR: u16 = 0x00E8; // 8 bytes
+pub const EVENT_LOG_ADDR: u16 = 0x0100;
+pub const EVENT_LOG_LEN: u16 = 0x0200; // 64 entries
+pub const PANIC_RECORD_ADDR: u16 = 0x0300; // 32 bytes
+
+/// CRC-8 (Dallas/Maxim, polynomial 0x31)
+pub fn crc8(data: &[u8]) -> u8 {
+    let mut crc: u8 = 0;
+
+    for byte in data {
+        crc ^= byte;
+
+        for _ in 0..8 {
+            if crc & 0x80 != 0 {
+                crc = (crc << 1) ^ 0x31;
+            } else {
+                crc <<= 1;
+            }
+        }
+    }
+
+    crc
+}
+
+/// Reads `data` followed by its CRC8 from `addr`.
+/// Returns false if the record was never written or is corrupted
+pub fn read_record(eeprom: &Eeprom, addr: u16, data: &mut [u8]) -> bool {
+    for (offset, byte) in data.iter_mut().enumerate() {
+        *byte = eeprom.read_byte(addr + offset as u16);
+    }
+
+    eeprom.read_byte(addr + data.len() as u16) == crc8(data)
+}
+
+/// Writes `data` followed by its CRC8 at `addr`
+pub fn write_record(eeprom: &mut Eeprom, addr: u16, data: &[u8]) {
+    for (offset, byte) in data.iter().enumerate() {
+        update_byte(eeprom, addr + offset as u16, *byte);
+    }
+
+    update_byte(eeprom, addr + data.len() as u16, crc8(data));
+}
+
+/// An EEPROM cell endures ~100k erase/write cycles: only write it when its content changes
+fn update_byte(eeprom: &mut Eeprom, addr: u16, byte: u8) {
+    if eeprom.read_byte(addr) != byte {
+        eeprom.write_byte(addr, byte);
+    }
+}
@@ -0,0 +1,58 @@
+use super::config::LightConfig;
+use micromath::F32Ext;
+
+/// Light on and off times (s since the local midnight)
+#[derive(Clone, Copy)]
+pub struct Daylight {
+    pub sunrise_s: u32,
+    pub sunset_s: u32,
+}
+
+impl Daylight {
+    /// Sunrise and sunset at the configured place, for the local day `days` (since
+    /// 2000-01-01). The day length is clamped to the configured range around the solar noon.
+    /// Low precision formulas (sunrise equation): within a few minutes up to the polar circles
+    pub fn compute(days: u32, config: &LightConfig) -> Self {
+        let latitude_deg = config.latitude_cdeg as f32 / 100.0;
+        let longitude_deg = config.longitude_cdeg as f32 / 100.0;
+
+        // Mean solar time (days since 2000-01-01 12:00 UTC), longitude east positive
+        let mean_solar_day = days as f32 - longitude_deg / 360.0;
+
+        let mean_anomaly_deg = (357.5291 + 0.985_600_3 * mean_solar_day) % 360.0;
+        let mean_anomaly = mean_anomaly_deg.to_radians();
+        let center_deg = 1.9148 * mean_anomaly.sin()
+            + 0.0200 * (2.0 * mean_anomaly).sin()
+            + 0.0003 * (3.0 * mean_anomaly).sin();
+        let ecliptic_longitude =
+            ((mean_anomaly_deg + center_deg + 180.0 + 102.9372) % 360.0).to_radians();
+
+        // UTC time of the solar noon (fraction of day)
+        let transit_day = 0.5 - longitude_deg / 360.0 + 0.0053 * mean_anomaly.sin()
+            - 0.0069 * (2.0 * ecliptic_longitude).sin();
+
+        let declination_sin = ecliptic_longitude.sin() * 23.4397_f32.to_radians().sin();
+        let declination_cos = (1.0 - declination_sin * declination_sin).sqrt();
+
+        // Sun center 0.833deg below the horizon (refraction, sun radius).
+        // Beyond the polar circles: no sunrise (0) or no sunset (1 day)
+        let latitude = latitude_deg.to_radians();
+        let hour_angle_cos = ((-0.833_f32).to_radians().sin() - latitude.sin() * declination_sin)
+            / (latitude.cos() * declination_cos);
+        let half_day = hour_angle_cos.clamp(-1.0, 1.0).acos() / (2.0 * core::f32::consts::PI);
+
+        let day_length_s = ((2.0 * half_day * 86_400.0) as u32).clamp(
+            (config.min_on_min as u32) * 60,
+            (config.max_on_min as u32) * 60,
+        );
+
+        let noon_s = (transit_day * 86_400.0) as i32 + (config.utc_offset_min as i32) * 60;
+        let sunrise_s = (noon_s - (day_length_s / 2) as i32).rem_euclid(86_400) as u32;
+
+        Self {
+            sunrise_s,
+            // Up to 24h: may be after the next midnight
+            sunset_s: sunrise_s + day_length_s,
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use super::Serial;
+use arduino_hal::hal::wdt::{Timeout, Wdt};
+use core::mem::MaybeUninit;
+
+// MCUSR as passed by Optiboot in r2, saved before the C runtime startup (kept out of the
+// RAM initialization)
+#[link_section = ".noinit"]
+static mut BOOT_RESET_FLAGS: MaybeUninit<u8> = MaybeUninit::uninit();
+
+/// First code run after the reset (.init0), before the stack and the RAM are set up: falls
+/// through to the C runtime startup
+#[naked]
+#[no_mangle]
+#[link_section = ".init0"]
+unsafe extern "C" fn save_boot_reset_flags() {
+    core::arch::asm!(
+        "sts {flags}, r2",
+        flags = sym BOOT_RESET_FLAGS,
+        options(noreturn)
+    );
+}
+
+/// Main loop tasks watched by the supervisor
+#[derive(Clone, Copy)]
+pub enum WatchedTask {
+    Alive = 0,
+    Light = 1,
+    Feeder = 2,
+    Serial = 3,
+}
+
+impl WatchedTask {
+    const COUNT: usize = 4;
+    const ALL: [WatchedTask; Self::COUNT] = [
+        WatchedTask::Alive,
+        WatchedTask::Light,
+        WatchedTask::Feeder,
+        WatchedTask::Serial,
+    ];
+
+    pub fn from_index(index: u8) -> Option<Self> {
+        Self::ALL.get(index as usize).copied()
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            WatchedTask::Alive => "alive",
+            WatchedTask::Light => "light",
+            WatchedTask::Feeder => "feeder",
+            WatchedTask::Serial => "serial",
+        }
+    }
+
+    /// Longest time allowed between two check-ins
+    fn deadline_us(&self) -> u64 {
+        match self {
+            WatchedTask::Alive => 1_000_000, // 1s
+            WatchedTask::Light => 2_000_000, // 2s
+            // Food waits for the filter pause before feeding
+            WatchedTask::Feeder => 60_000_000, // 1min
+            WatchedTask::Serial => 2_000_000,  // 2s
+        }
+    }
+}
+
+/// MCUSR reset flags
+#[derive(Clone, Copy)]
+pub struct ResetCause {
+    flags: u8,
+}
+
+impl ResetCause {
+    const POWER_ON: u8 = 1 << 0;
+    const EXTERNAL: u8 = 1 << 1;
+    const BROWN_OUT: u8 = 1 << 2;
+    const WATCHDOG: u8 = 1 << 3;
+    const ALL: u8 = Self::POWER_ON | Self::EXTERNAL | Self::BROWN_OUT | Self::WATCHDOG;
+
+    /// Optiboot clears MCUSR before starting the application: the flags it passed in r2 are
+    /// used instead (ignored if not a valid MCUSR value, e.g. another bootloader)
+    pub fn new(mcusr: u8) -> Self {
+        if mcusr != 0 {
+            return Self { flags: mcusr };
+        }
+
+        let boot_flags = unsafe { BOOT_RESET_FLAGS.assume_init() };
+        let flags = if boot_flags & !Self::ALL == 0 {
+            boot_flags
+        } else {
+            0
+        };
+
+        Self { flags }
+    }
+
+    pub fn bits(&self) -> u8 {
+        self.flags
+    }
+
+    pub fn is_watchdog(&self) -> bool {
+        self.flags & Self::WATCHDOG != 0
+    }
+
+    pub fn write(&self, serial: &mut Serial) {
+        ufmt::uwrite!(serial, "Reset cause:").unwrap();
+
+        if self.flags & Self::POWER_ON != 0 {
+            ufmt::uwrite!(serial, " power-on").unwrap();
+        }
+        if self.flags & Self::EXTERNAL != 0 {
+            ufmt::uwrite!(serial, " external").unwrap();
+        }
+        if self.flags & Self::BROWN_OUT != 0 {
+            ufmt::uwrite!(serial, " brown-out").unwrap();
+        }
+        if self.flags & Self::WATCHDOG != 0 {
+            ufmt::uwrite!(serial, " watchdog").unwrap();
+        }
+        if self.flags == 0 {
+            // Cleared by a bootloader that does not pass them
+            ufmt::uwrite!(serial, " unknown").unwrap();
+        }
+
+        ufmt::uwriteln!(serial, "\r").unwrap();
+    }
+}
+
+/// Feeds the hardware watchdog only while every task checks in within its deadline:
+/// a hung task resets the MCU. A task checks in once it has made progress, with the time
+/// it completed.
+pub struct Supervisor {
+    watchdog: Wdt,
+    check_in_us: [u64; WatchedTask::COUNT],
+    // Checked in after its deadline
+    late_task: Option<WatchedTask>,
+    is_stalled: bool,
+}
+
+impl Supervisor {
+    // Longest period of the AVR watchdog: a blocking step (e.g. one feeder move)
+    // must check in before it
+    const WATCHDOG_TIMEOUT: Timeout = Timeout::Ms8000;
+    pub const WATCHDOG_TIMEOUT_MS: u32 = 8_000;
+
+    pub fn new(mut watchdog: Wdt) -> Self {
+        watchdog.start(Self::WATCHDOG_TIMEOUT).unwrap();
+
+        Self {
+            watchdog,
+            check_in_us: [0; WatchedTask::COUNT],
+            late_task: None,
+            is_stalled: false,
+        }
+    }
+
+    /// The time since the previous check-in includes the run of the task: a slow run is late
+    pub fn check_in(&mut self, task: WatchedTask, t_us: u64) {
+        if self.late_task.is_none() && self.is_late(task, t_us) {
+            self.late_task = Some(task);
+        }
+
+        self.check_in_us[task as usize] = t_us;
+    }
+
+    /// For the blocking sequences (e.g. food delivery): the other tasks are held by design,
+    /// so the whole loop is considered alive
+    pub fn blocking_step(&mut self, t_us: u64) {
+        self.check_in_us = [t_us; WatchedTask::COUNT];
+        self.watchdog.feed();
+    }
+
+    /// Returns the late task when a stall is detected: the watchdog is not fed any more
+    /// and resets the MCU
+    pub fn update(&mut self, t_us: u64) -> Option<WatchedTask> {
+        if self.is_stalled {
+            return None;
+        }
+
+        // Then the tasks not making progress
+        let late_task = self.late_task.or_else(|| {
+            WatchedTask::ALL
+                .into_iter()
+                .find(|task| self.is_late(*task, t_us))
+        });
+
+        match late_task {
+            Some(task) => {
+                self.is_stalled = true;
+
+                Some(task)
+            }
+            None => {
+                self.watchdog.feed();
+
+                None
+            }
+        }
+    }
+
+    // Saturating: the check-in times may be ahead after the daily time reset
+    fn is_late(&self, task: WatchedTask, t_us: u64) -> bool {
+        t_us.saturating_sub(self.check_in_us[task as usize]) > task.deadline_us()
+    }
+}
@@ -0,0 +1,101 @@
+use super::{light::Photoperiod, Serial};
+use crate::drivers::{
+    adc::Adc,
+    time::sys_timer::{ImplTimer, SysTimer},
+    twi::Twi,
+};
+use arduino_hal::Eeprom;
+
+/// Shared state and resources handed to the tasks
+pub struct TaskContext<'a> {
+    pub t_us: u64,
+    pub time_of_day_us: u64,
+    pub day_us: u64,
+    /// Wall time (s since 2000-01-01), once the clock is set
+    pub wall_s: Option<u32>,
+    pub photoperiod: Photoperiod,
+    pub serial: &'a mut Serial,
+    pub eeprom: &'a mut Eeprom,
+    pub adc: &'a mut Adc,
+    pub i2c: &'a mut Twi,
+}
+
+pub trait Task {
+    fn name(&self) -> &'static str;
+
+    /// Called at each day start (the system time has just been reset)
+    fn init(&mut self, _context: &mut TaskContext) {}
+
+    fn update(&mut self, context: &mut TaskContext);
+
+    /// Earliest time the next update is needed. None: at every loop
+    fn next_wake_us(&self) -> Option<u64> {
+        None
+    }
+}
+
+#[derive(Clone, Copy)]
+struct TaskStats {
+    name: &'static str,
+    last_us: u32,
+    max_us: u32,
+}
+
+/// Runs the tasks in order. The tasks are borrowed for each run (no heap, no static
+/// storage of the modules): the scheduler only keeps their run time statistics.
+pub struct Scheduler<const N: usize> {
+    stats: [TaskStats; N],
+}
+
+impl<const N: usize> Scheduler<N> {
+    pub fn new() -> Self {
+        Self {
+            stats: [TaskStats {
+                name: "",
+                last_us: 0,
+                max_us: 0,
+            }; N],
+        }
+    }
+
+    pub fn init(&mut self, tasks: &mut [&mut dyn Task; N], context: &mut TaskContext) {
+        for (task, stats) in tasks.iter_mut().zip(self.stats.iter_mut()) {
+            stats.name = task.name();
+            task.init(context);
+        }
+    }
+
+    /// The run times have the resolution of the system timer
+    pub fn run<WhichTimer: ImplTimer>(
+        &mut self,
+        tasks: &mut [&mut dyn Task; N],
+        context: &mut TaskContext,
+        sys_timer: &SysTimer<WhichTimer>,
+    ) {
+        for (task, stats) in tasks.iter_mut().zip(self.stats.iter_mut()) {
+            if matches!(task.next_wake_us(), Some(wake_us) if context.t_us < wake_us) {
+                continue;
+            }
+
+            let t_start_us = sys_timer.micros();
+            task.update(context);
+            let run_us = sys_timer.micros().saturating_sub(t_start_us) as u32;
+
+            stats.last_us = run_us;
+            stats.max_us = stats.max_us.max(run_us);
+        }
+    }
+
+    pub fn report(&self, serial: &mut Serial) {
+        for stats in self.stats.iter() {
+            ufmt::uwriteln!(
+                serial,
+                "{}: last {}us, max {}us\r",
+                stats.name,
+                stats.last_us,
+                stats.max_us
+            )
+            .unwrap();
+        }
+    }
+}
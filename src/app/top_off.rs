@@ -0,0 +1,154 @@
+use super::task::{Task, TaskContext};
+use crate::drivers::{switch::Switch, time::timer::Timer};
+use arduino_hal::port::{
+    mode::{Input, Output, PullUp},
+    Pin, PinOps,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum LockoutCause {
+    /// The pump ran for the maximum time without the float switch releasing:
+    /// stuck float or empty reservoir
+    PumpTimeout,
+    /// The high level backup switch has triggered: main float switch failure
+    HighLevel,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TopOffState {
+    Idle,
+    Pumping { t_start_us: u64 },
+    DailyLimitReached,
+    LockedOut(LockoutCause),
+}
+
+/// Auto top-off:
+/// - Float switch (active when the water level is low) requests a refill
+/// - High level backup switch (active when the water level is too high) always cuts the pump
+pub struct TopOff<FloatPin: PinOps, HighLevelPin: PinOps, PumpPin: PinOps> {
+    float_switch: Switch<FloatPin>,
+    high_level_switch: Switch<HighLevelPin>,
+    pump: Pin<Output, PumpPin>,
+    pump_timer: Timer,
+    state: TopOffState,
+    pumped_today_us: u64,
+}
+
+impl<FloatPin: PinOps, HighLevelPin: PinOps, PumpPin: PinOps>
+    TopOff<FloatPin, HighLevelPin, PumpPin>
+{
+    // The water surface moves: the float has to stay low for a while before refilling
+    const FLOAT_DEBOUNCE_US: u64 = 5_000_000; // 5s
+    const HIGH_LEVEL_DEBOUNCE_US: u64 = 200_000; // 200ms
+
+    const MAX_PUMP_RUN_US: u64 = 60 * 1_000 * 1_000; // 1min per event
+    const MAX_DAILY_PUMP_RUN_US: u64 = 5 * 60 * 1_000 * 1_000; // 5min per day
+
+    pub fn new(
+        float_pin: Pin<Input<PullUp>, FloatPin>,
+        high_level_pin: Pin<Input<PullUp>, HighLevelPin>,
+        mut pump_pin: Pin<Output, PumpPin>,
+    ) -> Self {
+        pump_pin.set_low();
+
+        Self {
+            float_switch: Switch::new(float_pin, Self::FLOAT_DEBOUNCE_US),
+            high_level_switch: Switch::new(high_level_pin, Self::HIGH_LEVEL_DEBOUNCE_US),
+            pump: pump_pin,
+            pump_timer: Timer::new(Self::MAX_PUMP_RUN_US),
+            state: TopOffState::Idle,
+            pumped_today_us: 0,
+        }
+    }
+
+    /// A lockout needs a human check (reservoir, float switch) before pumping again
+    pub fn clear_lockout(&mut self) {
+        if let TopOffState::LockedOut(_) = self.state {
+            self.state = TopOffState::Idle;
+        }
+    }
+
+    pub fn get_state(&self) -> TopOffState {
+        self.state
+    }
+
+    fn start_pump(&mut self, t_us: u64) {
+        self.pump.set_high();
+        self.pump_timer.start(t_us);
+        self.state = TopOffState::Pumping { t_start_us: t_us };
+    }
+
+    fn stop_pump(&mut self, t_us: u64) {
+        self.pump.set_low();
+        self.pump_timer.stop();
+
+        if let TopOffState::Pumping { t_start_us } = self.state {
+            self.pumped_today_us += t_us.saturating_sub(t_start_us);
+        }
+    }
+}
+
+impl<FloatPin: PinOps, HighLevelPin: PinOps, PumpPin: PinOps> Task
+    for TopOff<FloatPin, HighLevelPin, PumpPin>
+{
+    fn name(&self) -> &'static str {
+        "top-off"
+    }
+
+    /// Restores the daily pump budget
+    fn init(&mut self, context: &mut TaskContext) {
+        self.pumped_today_us = 0;
+
+        // The pump run started before the time reset: restart the accounting now
+        if let TopOffState::Pumping { .. } = self.state {
+            self.state = TopOffState::Pumping {
+                t_start_us: context.t_us,
+            };
+            self.pump_timer.start(context.t_us);
+        }
+
+        if self.state == TopOffState::DailyLimitReached {
+            self.state = TopOffState::Idle;
+        }
+    }
+
+    fn update(&mut self, context: &mut TaskContext) {
+        let t_us = context.t_us;
+
+        self.float_switch.update(t_us);
+        self.high_level_switch.update(t_us);
+
+        // Backup switch: whatever the state, the pump is cut
+        if self.high_level_switch.is_active() {
+            if !matches!(self.state, TopOffState::LockedOut(_)) {
+                self.stop_pump(t_us);
+                self.state = TopOffState::LockedOut(LockoutCause::HighLevel);
+            }
+
+            return;
+        }
+
+        match self.state {
+            TopOffState::Idle => {
+                if self.float_switch.is_active() {
+                    self.start_pump(t_us);
+                }
+            }
+            TopOffState::Pumping { t_start_us } => {
+                if !self.float_switch.is_active() {
+                    self.stop_pump(t_us);
+                    self.state = TopOffState::Idle;
+                } else if let Ok(true) = self.pump_timer.has_expired(t_us) {
+                    self.stop_pump(t_us);
+                    self.state = TopOffState::LockedOut(LockoutCause::PumpTimeout);
+                } else if self.pumped_today_us + t_us.saturating_sub(t_start_us)
+                    >= Self::MAX_DAILY_PUMP_RUN_US
+                {
+                    self.stop_pump(t_us);
+                    self.state = TopOffState::DailyLimitReached;
+                }
+            }
+            TopOffState::DailyLimitReached | TopOffState::LockedOut(_) => {}
+        }
+    }
+}
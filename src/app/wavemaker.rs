@@ -0,0 +1,211 @@
+use super::{
+    config::WaveConfig,
+    random::Random,
+    task::{Task, TaskContext},
+};
+use crate::drivers::time::timer::Timer;
+use arduino_hal::{
+    port::{mode::PwmOutput, Pin},
+    simple_pwm::{PwmPinOps, Timer2Pwm},
+};
+
+#[derive(Clone, Copy)]
+pub enum WavePattern {
+    /// Max power
+    Constant,
+    /// Min/max power every half period
+    Pulse,
+    /// Random power in [min, max] for a random time in [period / 4, period]
+    RandomGusts,
+    /// Pump A at max while pump B is at min, swapped every half period
+    Alternating,
+    /// Slow ramp between min and max power over the period
+    Night,
+}
+
+impl WavePattern {
+    /// Config value
+    pub fn code(&self) -> u8 {
+        match self {
+            WavePattern::Constant => 0,
+            WavePattern::Pulse => 1,
+            WavePattern::RandomGusts => 2,
+            WavePattern::Alternating => 3,
+            WavePattern::Night => 4,
+        }
+    }
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0 => Some(WavePattern::Constant),
+            1 => Some(WavePattern::Pulse),
+            2 => Some(WavePattern::RandomGusts),
+            3 => Some(WavePattern::Alternating),
+            4 => Some(WavePattern::Night),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct WaveSettings {
+    pub pattern: WavePattern,
+    pub min_power_pct: u8,
+    pub max_power_pct: u8,
+    pub period_s: u16,
+}
+
+/// One or two DC circulation pumps driven by Timer2 PWM (D3: OC2B, D11: OC2A)
+pub struct Wavemaker<PumpAPin: PwmPinOps<Timer2Pwm>, PumpBPin: PwmPinOps<Timer2Pwm>> {
+    pump_a: Pin<PwmOutput<Timer2Pwm>, PumpAPin>,
+    pump_b: Option<Pin<PwmOutput<Timer2Pwm>, PumpBPin>>,
+    config: WaveConfig,
+    night_mode: bool,
+    feed_mode: bool,
+    step_timer: Timer,
+    phase: bool,
+    gust_power_pct: u8,
+    random: Random,
+}
+
+impl<PumpAPin: PwmPinOps<Timer2Pwm>, PumpBPin: PwmPinOps<Timer2Pwm>> Wavemaker<PumpAPin, PumpBPin> {
+    const RANDOM_SEED: u32 = 0x5EED_0001;
+
+    pub fn new(
+        mut pump_a: Pin<PwmOutput<Timer2Pwm>, PumpAPin>,
+        mut pump_b: Option<Pin<PwmOutput<Timer2Pwm>, PumpBPin>>,
+        config: &WaveConfig,
+    ) -> Self {
+        pump_a.set_duty(0);
+        pump_a.enable();
+
+        if let Some(pump_b) = pump_b.as_mut() {
+            pump_b.set_duty(0);
+            pump_b.enable();
+        }
+
+        Self {
+            pump_a,
+            pump_b,
+            config: *config,
+            night_mode: false,
+            feed_mode: false,
+            step_timer: Timer::new(0),
+            phase: false,
+            gust_power_pct: 0,
+            random: Random::new(Self::RANDOM_SEED),
+        }
+    }
+
+    pub fn set_config(&mut self, config: &WaveConfig) {
+        self.config = *config;
+
+        // Restart the pattern with the new settings
+        self.step_timer.stop();
+    }
+
+    /// Stops the flow while feeding: food stays in the tank
+    pub fn set_feed_mode(&mut self, feed_mode: bool) {
+        self.feed_mode = feed_mode;
+    }
+
+    pub fn set_night_mode(&mut self, night_mode: bool) {
+        if night_mode != self.night_mode {
+            self.night_mode = night_mode;
+
+            // Restart the pattern with the new settings
+            self.step_timer.stop();
+        }
+    }
+
+    fn restart_step(&mut self, t_us: u64, timeout_us: u64) {
+        self.step_timer = Timer::new(timeout_us);
+        self.step_timer.start(t_us);
+    }
+
+    fn set_power(&mut self, power_a_pct: u8, power_b_pct: u8) {
+        self.pump_a.set_duty(Self::duty(power_a_pct));
+
+        if let Some(pump_b) = self.pump_b.as_mut() {
+            pump_b.set_duty(Self::duty(power_b_pct));
+        }
+    }
+
+    fn duty(power_pct: u8) -> u8 {
+        ((power_pct.min(100) as u16) * 255 / 100) as u8
+    }
+}
+
+impl<PumpAPin: PwmPinOps<Timer2Pwm>, PumpBPin: PwmPinOps<Timer2Pwm>> Task
+    for Wavemaker<PumpAPin, PumpBPin>
+{
+    fn name(&self) -> &'static str {
+        "wavemaker"
+    }
+
+    /// The pattern restarts with the system time
+    fn init(&mut self, _context: &mut TaskContext) {
+        self.step_timer.stop();
+    }
+
+    fn update(&mut self, context: &mut TaskContext) {
+        let t_us = context.t_us;
+
+        if self.feed_mode {
+            self.set_power(0, 0);
+            return;
+        }
+
+        let settings = if self.night_mode {
+            self.config.night
+        } else {
+            self.config.day
+        };
+        let (min, max) = (settings.min_power_pct, settings.max_power_pct);
+        let period_us = (settings.period_s as u64) * 1_000_000;
+
+        let step_expired = !matches!(self.step_timer.has_expired(t_us), Ok(false));
+
+        match settings.pattern {
+            WavePattern::Constant => self.set_power(max, max),
+            WavePattern::Pulse | WavePattern::Alternating => {
+                if step_expired {
+                    self.phase = !self.phase;
+                    self.restart_step(t_us, period_us / 2);
+                }
+
+                let (power_a, power_b) = match (settings.pattern, self.phase) {
+                    (WavePattern::Alternating, true) => (max, min),
+                    (WavePattern::Alternating, false) => (min, max),
+                    (_, true) => (max, max),
+                    (_, false) => (min, min),
+                };
+                self.set_power(power_a, power_b);
+            }
+            WavePattern::RandomGusts => {
+                if step_expired {
+                    self.gust_power_pct = self.random.range(min as u32, max as u32) as u8;
+
+                    let gust_us = self.random.range((period_us / 4) as u32, period_us as u32);
+                    self.restart_step(t_us, gust_us as u64);
+                }
+
+                self.set_power(self.gust_power_pct, self.gust_power_pct);
+            }
+            WavePattern::Night => {
+                // Triangle: min -> max -> min over the period
+                let position_us = t_us % period_us.max(1);
+                let half_period_us = (period_us / 2).max(1);
+                let ramp_us = if position_us < half_period_us {
+                    position_us
+                } else {
+                    period_us - position_us
+                };
+
+                let power =
+                    min as u64 + (max.saturating_sub(min) as u64) * ramp_us / half_period_us;
+                self.set_power(power as u8, power as u8);
+            }
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use super::{clock::weekday, config::WeatherConfig, random::Random};
+use crate::drivers::time::timer::Timer;
+
+enum WeatherState {
+    Clear,
+    Cloud { t_start_us: u64, duration_us: u64 },
+    Storm { flashes_left: u8, flash_on: bool },
+}
+
+/// Cloud passes and lightning storms over the daylight. Each day is drawn from a seeded
+/// pseudo random generator: the same seed and day give the same weather.
+pub struct Weather {
+    config: WeatherConfig,
+    random: Random,
+    enabled: bool,
+    // Seed of the day while the clock is not set
+    days_since_boot: u32,
+    state: WeatherState,
+    // Next cloud (clear), flash step (storm)
+    step_timer: Timer,
+    storm_start_timer: Timer,
+    storm_end_timer: Timer,
+}
+
+impl Weather {
+    const MIN_CLOUD_US: u64 = 5_000_000; // 5s
+
+    // Dimming and brightening time of a cloud, at most
+    const CLOUD_RAMP_US: u64 = 5_000_000; // 5s
+    const MIN_STORM_US: u64 = 5 * 60 * 1_000_000; // 5min
+    const STORM_DEPTH_PCT: u32 = 80;
+    // No storm within 1h of the light transitions
+    const STORM_MARGIN_US: u64 = 60 * 60 * 1_000_000; // 1h
+
+    // Lightning: bursts of 1 to 3 flashes every 5 to 30s
+    const MAX_FLASHES: u32 = 3;
+    const FLASH_MIN_US: u32 = 50_000; // 50ms
+    const FLASH_MAX_US: u32 = 150_000; // 150ms
+    const FLASH_GAP_US: u32 = 80_000; // 80ms
+    const BURST_MIN_GAP_US: u32 = 5_000_000; // 5s
+    const BURST_MAX_GAP_US: u32 = 30_000_000; // 30s
+
+    pub fn new(config: &WeatherConfig) -> Self {
+        Self {
+            config: *config,
+            random: Random::new(config.seed as u32),
+            enabled: false,
+            days_since_boot: 0,
+            state: WeatherState::Clear,
+            step_timer: Timer::new(0),
+            storm_start_timer: Timer::new(0),
+            storm_end_timer: Timer::new(0),
+        }
+    }
+
+    /// Applied from the next day
+    pub fn set_config(&mut self, config: &WeatherConfig) {
+        self.config = *config;
+    }
+
+    /// Draws the weather of a day. `wall_day`: days since 2000-01-01, None while the clock
+    /// is not set (no day off then, unless the weather is off every day)
+    pub fn start_day(&mut self, t_us: u64, wall_day: Option<u32>, daylight_us: u64) {
+        let day = wall_day.unwrap_or(self.days_since_boot);
+        self.days_since_boot += 1;
+
+        self.random = Random::new(((self.config.seed as u32) << 16) ^ day);
+        self.state = WeatherState::Clear;
+        self.step_timer.stop();
+        self.storm_start_timer.stop();
+        self.storm_end_timer.stop();
+
+        self.enabled = match wall_day {
+            Some(day) => self.config.days & (1 << weekday(day)) != 0,
+            None => self.config.days != 0,
+        };
+        if !self.enabled {
+            return;
+        }
+
+        if self.config.cloud_depth_pct > 0 {
+            let gap_us = self.cloud_gap_us();
+            self.restart_step(t_us, gap_us);
+        }
+
+        let storm_max_us = (self.config.storm_max_min as u64) * 60 * 1_000_000;
+        if self.random.range(1, 100) <= self.config.storm_pct as u32
+            && daylight_us > 2 * Self::STORM_MARGIN_US + storm_max_us
+        {
+            let latest_start_us = daylight_us - Self::STORM_MARGIN_US - storm_max_us;
+            let start_us = self.random_us(Self::STORM_MARGIN_US, latest_start_us);
+            let duration_us = self.random_us(Self::MIN_STORM_US, storm_max_us);
+
+            self.storm_start_timer = Timer::new(start_us);
+            self.storm_start_timer.start(t_us);
+            self.storm_end_timer = Timer::new(start_us + duration_us);
+            self.storm_end_timer.start(t_us);
+        }
+    }
+
+    /// Daylight level (255: clear sky)
+    pub fn update(&mut self, t_us: u64) -> u8 {
+        if !self.enabled {
+            return u8::MAX;
+        }
+
+        if let Ok(true) = self.storm_start_timer.has_expired(t_us) {
+            self.storm_start_timer.stop();
+            self.state = WeatherState::Storm {
+                flashes_left: 0,
+                flash_on: false,
+            };
+            let gap_us = self
+                .random
+                .range(Self::BURST_MIN_GAP_US, Self::BURST_MAX_GAP_US);
+            self.restart_step(t_us, gap_us as u64);
+        }
+
+        if let Ok(true) = self.storm_end_timer.has_expired(t_us) {
+            self.storm_end_timer.stop();
+            self.state = WeatherState::Clear;
+            self.step_timer.stop();
+            if self.config.cloud_depth_pct > 0 {
+                let gap_us = self.cloud_gap_us();
+                self.restart_step(t_us, gap_us);
+            }
+        }
+
+        let step_expired = matches!(self.step_timer.has_expired(t_us), Ok(true));
+
+        match self.state {
+            WeatherState::Clear => {
+                if step_expired {
+                    let max_us = (self.config.cloud_max_s as u64) * 1_000_000;
+                    self.state = WeatherState::Cloud {
+                        t_start_us: t_us,
+                        duration_us: self.random_us(Self::MIN_CLOUD_US, max_us),
+                    };
+                    self.step_timer.stop();
+                }
+
+                u8::MAX
+            }
+            WeatherState::Cloud {
+                t_start_us,
+                duration_us,
+            } => {
+                let elapsed_us = t_us - t_start_us;
+                if elapsed_us >= duration_us {
+                    self.state = WeatherState::Clear;
+                    let gap_us = self.cloud_gap_us();
+                    self.restart_step(t_us, gap_us);
+
+                    return u8::MAX;
+                }
+
+                // Trapezoid: dims, stays, brightens
+                let ramp_us = Self::CLOUD_RAMP_US.min(duration_us / 2).max(1);
+                let position_us = elapsed_us.min(duration_us - elapsed_us).min(ramp_us);
+                let depth = (u8::MAX as u64) * (self.config.cloud_depth_pct as u64) / 100;
+
+                (u8::MAX as u64 - depth * position_us / ramp_us) as u8
+            }
+            WeatherState::Storm {
+                flashes_left,
+                flash_on,
+            } => {
+                if step_expired {
+                    if flash_on {
+                        self.state = WeatherState::Storm {
+                            flashes_left,
+                            flash_on: false,
+                        };
+                        let gap_us = if flashes_left > 0 {
+                            Self::FLASH_GAP_US
+                        } else {
+                            self.random
+                                .range(Self::BURST_MIN_GAP_US, Self::BURST_MAX_GAP_US)
+                        };
+                        self.restart_step(t_us, gap_us as u64);
+                    } else {
+                        let flashes_left = if flashes_left > 0 {
+                            flashes_left
+                        } else {
+                            self.random.range(1, Self::MAX_FLASHES) as u8
+                        };
+                        self.state = WeatherState::Storm {
+                            flashes_left: flashes_left - 1,
+                            flash_on: true,
+                        };
+                        let flash_us = self.random.range(Self::FLASH_MIN_US, Self::FLASH_MAX_US);
+                        self.restart_step(t_us, flash_us as u64);
+                    }
+                }
+
+                match self.state {
+                    WeatherState::Storm { flash_on: true, .. } => u8::MAX,
+                    _ => (u8::MAX as u32 * (100 - Self::STORM_DEPTH_PCT) / 100) as u8,
+                }
+            }
+        }
+    }
+
+    /// A cloud or a storm dims the daylight
+    pub fn is_active(&self) -> bool {
+        !matches!(self.state, WeatherState::Clear)
+    }
+
+    /// Clear sky: at the next event. Cloud or storm: at every loop
+    pub fn next_wake_us(&self) -> Option<u64> {
+        if !self.enabled {
+            return Some(u64::MAX);
+        }
+
+        match self.state {
+            WeatherState::Clear => {
+                let storm_us = self.storm_start_timer.get_expiry_us();
+                let cloud_us = self.step_timer.get_expiry_us();
+
+                Some(
+                    storm_us
+                        .unwrap_or(u64::MAX)
+                        .min(cloud_us.unwrap_or(u64::MAX)),
+                )
+            }
+            WeatherState::Cloud { .. } | WeatherState::Storm { .. } => None,
+        }
+    }
+
+    /// Clear time before a cloud: the configured clouds per hour on average
+    fn cloud_gap_us(&mut self) -> u64 {
+        let mean_us = 3_600 * 1_000_000 / (self.config.clouds_per_h.max(1) as u64);
+
+        self.random_us(mean_us / 2, mean_us * 3 / 2)
+    }
+
+    /// Uniform in [min, max], at a 1ms resolution (u32 range)
+    fn random_us(&mut self, min_us: u64, max_us: u64) -> u64 {
+        (self
+            .random
+            .range((min_us / 1_000) as u32, (max_us / 1_000) as u32) as u64)
+            * 1_000
+    }
+
+    fn restart_step(&mut self, t_us: u64, step_us: u64) {
+        self.step_timer = Timer::new(step_us);
+        self.step_timer.start(t_us);
+    }
+}
@@ -0,0 +1,46 @@
+/// Median of the N last samples: rejects spikes
+pub struct MedianFilter<const N: usize> {
+    samples: [u16; N],
+    count: usize,
+    index: usize,
+}
+
+impl<const N: usize> MedianFilter<N> {
+    pub fn new() -> Self {
+        Self {
+            samples: [0; N],
+            count: 0,
+            index: 0,
+        }
+    }
+
+    pub fn push(&mut self, sample: u16) {
+        self.samples[self.index] = sample;
+
+        self.index = (self.index + 1) % N;
+        if self.count < N {
+            self.count += 1;
+        }
+    }
+
+    /// None until the window is full
+    pub fn median(&self) -> Option<u16> {
+        self.sorted().map(|sorted| sorted[N / 2])
+    }
+
+    /// Max - min of the window: None until the window is full
+    pub fn spread(&self) -> Option<u16> {
+        self.sorted().map(|sorted| sorted[N - 1] - sorted[0])
+    }
+
+    fn sorted(&self) -> Option<[u16; N]> {
+        if self.count < N {
+            return None;
+        }
+
+        let mut sorted = self.samples;
+        sorted.sort_unstable();
+
+        Some(sorted)
+    }
+}
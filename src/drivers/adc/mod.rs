@@ -0,0 +1,241 @@
+mod filter;
+
+use super::time::timer::Timer;
+use avr_device::interrupt::Mutex;
+use core::cell::Cell;
+pub use filter::MedianFilter;
+
+// Only the wired inputs: A0..A5 are used as digital I/O and I2C
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    Adc6, // Nano only: analog input only
+    Adc7, // Nano only: analog input only
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Reference {
+    Avcc,
+}
+
+impl Reference {
+    const fn millivolts(&self) -> u32 {
+        match self {
+            Reference::Avcc => 5_000,
+        }
+    }
+}
+
+/// Conversion requested by a sensor
+pub struct AdcRequest {
+    pub channel: Channel,
+    pub reference: Reference,
+    /// Each extra bit costs 4 times more samples: 0 => 1 sample (10 bits), 2 => 16 samples (12 bits)
+    pub oversampling_bits: u8,
+}
+
+impl AdcRequest {
+    const MAX_OVERSAMPLING_BITS: u8 = 4;
+
+    pub const fn new(channel: Channel, reference: Reference, oversampling_bits: u8) -> Self {
+        Self {
+            channel,
+            reference,
+            oversampling_bits,
+        }
+    }
+
+    pub const fn resolution_bits(&self) -> u8 {
+        10 + self.oversampling_bits
+    }
+
+    pub fn to_millivolts(&self, raw: u16) -> u32 {
+        ((raw as u32) * self.reference.millivolts()) >> self.resolution_bits()
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Accumulator {
+    sum: u32,
+    count: u16,
+    target: u16,
+    // The first conversion after a channel change is less accurate
+    discard: bool,
+}
+
+impl Accumulator {
+    const fn new() -> Self {
+        Self {
+            sum: 0,
+            count: 0,
+            target: 0,
+            discard: false,
+        }
+    }
+}
+
+static ACCUMULATOR: Mutex<Cell<Accumulator>> = Mutex::new(Cell::new(Accumulator::new()));
+
+enum AdcState {
+    Idle,
+    Settling { channel: Channel },
+    Converting { channel: Channel },
+    Done { channel: Channel, raw: u16 },
+}
+
+/// Interrupt driven ADC: conversions (and oversampling) run in the background of the main loop
+pub struct Adc {
+    adc: arduino_hal::pac::ADC,
+    reference: Option<Reference>,
+    oversampling_bits: u8,
+    settling_timer: Timer,
+    state: AdcState,
+}
+
+impl Adc {
+    // AREF capacitor (100nF) has to charge/discharge after a reference change
+    const REFERENCE_SETTLING_US: u64 = 5_000; // 5ms
+
+    pub fn new(adc: arduino_hal::pac::ADC) -> Self {
+        // ADCSRA (ADC Control and Status Register A): ADC enabled, ADIE conversion complete interrupt
+        // ADPS: 16MHz/128 = 125kHz ADC clock (50kHz..200kHz for 10 bits) => ~104us per conversion
+        adc.adcsra
+            .write(|w| w.aden().set_bit().adie().set_bit().adps().prescaler_128());
+
+        Self {
+            adc,
+            reference: None,
+            oversampling_bits: 0,
+            settling_timer: Timer::new(Self::REFERENCE_SETTLING_US),
+            state: AdcState::Idle,
+        }
+    }
+
+    /// Non blocking: starts the conversion if the ADC is free, returns the result once done.
+    /// To be called until it returns a value
+    pub fn read(&mut self, t_us: u64, request: &AdcRequest) -> Option<u16> {
+        match self.state {
+            AdcState::Idle => {
+                self.start(t_us, request);
+                None
+            }
+            AdcState::Settling { channel } => {
+                if channel == request.channel {
+                    if let Ok(true) = self.settling_timer.has_expired(t_us) {
+                        self.settling_timer.stop();
+                        self.convert(channel);
+                    }
+                }
+                None
+            }
+            AdcState::Converting { channel } => {
+                if let Some(raw) = self.conversion_result() {
+                    self.state = AdcState::Done { channel, raw };
+                }
+
+                self.take_result(request.channel)
+            }
+            AdcState::Done { .. } => self.take_result(request.channel),
+        }
+    }
+
+    fn start(&mut self, t_us: u64, request: &AdcRequest) {
+        let reference_changed = self.reference != Some(request.reference);
+
+        self.reference = Some(request.reference);
+        self.oversampling_bits = request
+            .oversampling_bits
+            .min(AdcRequest::MAX_OVERSAMPLING_BITS);
+
+        self.select(request.channel, request.reference);
+
+        if reference_changed {
+            self.settling_timer.start(t_us);
+            self.state = AdcState::Settling {
+                channel: request.channel,
+            };
+        } else {
+            self.convert(request.channel);
+        }
+    }
+
+    fn select(&mut self, channel: Channel, reference: Reference) {
+        // ADMUX (ADC Multiplexer Selection Register): REFS (Reference Selection), MUX (Analog Channel Selection)
+        self.adc.admux.write(|w| {
+            match reference {
+                Reference::Avcc => w.refs().avcc(),
+            };
+
+            match channel {
+                Channel::Adc6 => w.mux().adc6(),
+                Channel::Adc7 => w.mux().adc7(),
+            }
+        });
+    }
+
+    fn convert(&mut self, channel: Channel) {
+        avr_device::interrupt::free(|cs| {
+            ACCUMULATOR.borrow(cs).set(Accumulator {
+                sum: 0,
+                count: 0,
+                target: 1 << (2 * self.oversampling_bits),
+                discard: true,
+            })
+        });
+
+        self.state = AdcState::Converting { channel };
+
+        // ADSC: start conversion, next ones are chained by the interrupt
+        self.adc.adcsra.modify(|_, w| w.adsc().set_bit());
+    }
+
+    fn conversion_result(&self) -> Option<u16> {
+        let accumulator = avr_device::interrupt::free(|cs| ACCUMULATOR.borrow(cs).get());
+
+        if accumulator.count == accumulator.target {
+            Some((accumulator.sum >> self.oversampling_bits) as u16)
+        } else {
+            None
+        }
+    }
+
+    fn take_result(&mut self, channel: Channel) -> Option<u16> {
+        if let AdcState::Done {
+            channel: done_channel,
+            raw,
+        } = self.state
+        {
+            if done_channel == channel {
+                self.state = AdcState::Idle;
+
+                return Some(raw);
+            }
+        }
+
+        None
+    }
+}
+
+#[avr_device::interrupt(atmega328p)]
+fn ADC() {
+    // Only the result register and ADSC are touched here, the driver does not access them while converting
+    let adc = unsafe { &*arduino_hal::pac::ADC::ptr() };
+    let sample = adc.adc.read().bits();
+
+    avr_device::interrupt::free(|cs| {
+        let borrowed_accumulator = ACCUMULATOR.borrow(cs);
+        let mut accumulator = borrowed_accumulator.get();
+
+        if accumulator.discard {
+            accumulator.discard = false;
+        } else {
+            accumulator.sum += sample as u32;
+            accumulator.count += 1;
+        }
+
+        if accumulator.count < accumulator.target {
+            adc.adcsra.modify(|_, w| w.adsc().set_bit());
+        }
+
+        borrowed_accumulator.set(accumulator)
+    })
+}
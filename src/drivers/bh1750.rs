@@ -0,0 +1,36 @@
+use super::twi::{I2cDevice, Twi, TwiError};
+
+/// BH1750 ambient light sensor (I2C): continuous high resolution mode, a new reading every
+/// 120ms (180ms max), 1 lux resolution up to 65535 lux
+pub struct Bh1750 {
+    device: I2cDevice,
+}
+
+impl Bh1750 {
+    /// ADDR pin low (high: 0x5C)
+    pub const ADDRESS_LOW: u8 = 0x23;
+
+    const POWER_ON: u8 = 0x01;
+    const CONTINUOUS_HIGH_RES: u8 = 0x10;
+
+    pub fn new(address: u8) -> Self {
+        Self {
+            device: I2cDevice::new(address),
+        }
+    }
+
+    /// Powers on and starts the measurements: the first reading is ready after 180ms
+    pub fn start(&self, bus: &mut Twi) -> Result<(), TwiError> {
+        self.device.write(bus, &[Self::POWER_ON])?;
+        self.device.write(bus, &[Self::CONTINUOUS_HIGH_RES])
+    }
+
+    /// Last measurement (lux)
+    pub fn read_lux(&self, bus: &mut Twi) -> Result<u16, TwiError> {
+        let mut bytes = [0; 2];
+        self.device.read(bus, &mut bytes)?;
+
+        // Count / 1.2 (default measurement time)
+        Ok(((u16::from_be_bytes(bytes) as u32) * 5 / 6) as u16)
+    }
+}
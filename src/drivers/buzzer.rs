@@ -0,0 +1,52 @@
+use arduino_hal::pac::TC1;
+
+/// Passive piezo buzzer on D10 (OC1B): Timer1 runs in CTC mode (OCR1A top) and toggles the
+/// pin in hardware at each compare match, no interrupt.
+///
+/// D10 is also the stepper IN4 and is owned by the stepper (as an output): the buzzer must be
+/// off while the feeder moves. With the stepper disabled, its driver only follows the tone.
+pub struct Buzzer {
+    timer_counter: TC1,
+}
+
+impl Buzzer {
+    // 16MHz / 8 => 2MHz timer clock: 31Hz..1MHz tones
+    const TIMER_CLOCK_HZ: u32 = 2_000_000;
+
+    pub fn new(timer_counter: TC1) -> Self {
+        // TCCR1 (Timer/Counter1 Control Registers): WGM1 mode 4, CTC with OCR1A top, stopped
+        timer_counter.tccr1a.reset();
+        timer_counter.tccr1b.write(|w| w.wgm1().bits(0b01));
+        // OC1B toggles once per period, at the counter start
+        timer_counter.ocr1b.write(|w| w.bits(0));
+
+        Self { timer_counter }
+    }
+
+    pub fn tone(&mut self, frequency_hz: u16) {
+        if frequency_hz == 0 {
+            self.off();
+            return;
+        }
+
+        // Two compare matches per period
+        let top = (Self::TIMER_CLOCK_HZ / (2 * frequency_hz as u32)).clamp(1, u16::MAX as u32) - 1;
+
+        self.timer_counter.ocr1a.write(|w| w.bits(top as u16));
+        self.timer_counter.tcnt1.reset();
+
+        // COM1B: OC1B toggled on compare match
+        self.timer_counter
+            .tccr1a
+            .write(|w| w.com1b().match_toggle());
+        self.timer_counter
+            .tccr1b
+            .modify(|_, w| w.cs1().prescale_8());
+    }
+
+    pub fn off(&mut self) {
+        self.timer_counter.tccr1b.modify(|_, w| w.cs1().no_clock());
+        // OC1B disconnected: the pin follows its port value (stepper IN4) again
+        self.timer_counter.tccr1a.reset();
+    }
+}
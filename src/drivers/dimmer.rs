@@ -0,0 +1,51 @@
+use arduino_hal::{
+    hal::port::PD5,
+    pac::TC0,
+    port::{mode::Output, Pin},
+};
+
+/// PWM output on OC0B (digital pin 5). Timer/Counter0 is shared with the system timer:
+/// it has to run in fast PWM mode (FastPwmTimer, ~1kHz with the prescaler 64 at 16MHz)
+pub struct Dimmer {
+    pin: Pin<Output, PD5>,
+    level: u8,
+}
+
+impl Dimmer {
+    pub fn new(mut pin: Pin<Output, PD5>) -> Self {
+        pin.set_low();
+
+        Self { pin, level: 0 }
+    }
+
+    /// 0: off .. 255: fully on. Both ends are plain outputs (no 1/256 pulse left)
+    pub fn set_level(&mut self, level: u8) {
+        if level == self.level {
+            return;
+        }
+
+        // Only the OC0B bits are changed: the system timer owns the rest of TC0
+        let timer_counter = unsafe { &*TC0::ptr() };
+
+        match level {
+            0 | u8::MAX => {
+                timer_counter.tccr0a.modify(|_, w| w.com0b().disconnected());
+                if level == 0 {
+                    self.pin.set_low();
+                } else {
+                    self.pin.set_high();
+                }
+            }
+            _ => {
+                timer_counter.ocr0b.write(|w| w.bits(level));
+                timer_counter.tccr0a.modify(|_, w| w.com0b().match_clear());
+            }
+        }
+
+        self.level = level;
+    }
+
+    pub fn level(&self) -> u8 {
+        self.level
+    }
+}
@@ -0,0 +1,126 @@
+use super::{TextDisplay, COLUMNS, ROWS};
+use crate::drivers::twi::{I2cDevice, Twi, TwiError};
+
+/// HD44780 20x4 character LCD through a PCF8574 I2C backpack, 4-bit mode.
+/// Expander pins: P0 RS, P1 RW (held low: write only), P2 E, P3 backlight, P4..P7 D4..D7
+pub struct Hd44780 {
+    device: I2cDevice,
+    backlight: bool,
+}
+
+impl Hd44780 {
+    /// PCF8574 with A0..A2 high (PCF8574A: 0x3F)
+    pub const ADDRESS: u8 = 0x27;
+
+    const RS: u8 = 1 << 0;
+    const E: u8 = 1 << 2;
+    const BACKLIGHT: u8 = 1 << 3;
+
+    // DDRAM address of each row start
+    const ROW_ADDRESSES: [u8; ROWS] = [0x00, 0x40, 0x14, 0x54];
+
+    const CLEAR: u8 = 0x01;
+    const ENTRY_MODE_INCREMENT: u8 = 0x06;
+    const DISPLAY_OFF: u8 = 0x08;
+    // No cursor, no blink
+    const DISPLAY_ON: u8 = 0x0C;
+    const FUNCTION_SET_4_BITS_2_LINES: u8 = 0x28;
+    const SET_DDRAM_ADDRESS: u8 = 0x80;
+
+    pub fn new(address: u8) -> Self {
+        Self {
+            device: I2cDevice::new(address),
+            backlight: true,
+        }
+    }
+
+    fn backlight_bit(&self) -> u8 {
+        if self.backlight {
+            Self::BACKLIGHT
+        } else {
+            0
+        }
+    }
+
+    /// Latched on the E falling edge. One I2C byte (90us at 100kHz) is a long enough E
+    /// pulse (450ns) and command execution time (37us)
+    fn write_nibble(&self, bus: &mut Twi, nibble: u8, flags: u8) -> Result<(), TwiError> {
+        let byte = (nibble << 4) | flags | self.backlight_bit();
+
+        self.device.write(bus, &[byte | Self::E, byte])
+    }
+
+    /// High nibble first
+    fn write_byte(&self, bus: &mut Twi, value: u8, flags: u8) -> Result<(), TwiError> {
+        let flags = flags | self.backlight_bit();
+        let high = (value & 0xF0) | flags;
+        let low = (value << 4) | flags;
+
+        self.device
+            .write(bus, &[high | Self::E, high, low | Self::E, low])
+    }
+
+    fn command(&self, bus: &mut Twi, command: u8) -> Result<(), TwiError> {
+        self.write_byte(bus, command, 0)
+    }
+}
+
+impl TextDisplay for Hd44780 {
+    /// Blocking for ~60ms (power-on and reset delays)
+    fn init(&mut self, bus: &mut Twi) -> Result<(), TwiError> {
+        // 40ms after the power-on before the first command
+        arduino_hal::delay_ms(50);
+
+        // Back to 8-bit mode whatever the current state (even half a byte sent), then 4-bit
+        self.write_nibble(bus, 0x03, 0)?;
+        arduino_hal::delay_ms(5);
+        self.write_nibble(bus, 0x03, 0)?;
+        arduino_hal::delay_us(150);
+        self.write_nibble(bus, 0x03, 0)?;
+        self.write_nibble(bus, 0x02, 0)?;
+
+        self.command(bus, Self::FUNCTION_SET_4_BITS_2_LINES)?;
+        self.command(bus, Self::DISPLAY_OFF)?;
+        self.command(bus, Self::CLEAR)?;
+        arduino_hal::delay_ms(2);
+        self.command(bus, Self::ENTRY_MODE_INCREMENT)?;
+
+        self.command(bus, Self::DISPLAY_ON)
+    }
+
+    fn write_text(
+        &mut self,
+        bus: &mut Twi,
+        row: usize,
+        column: usize,
+        text: &[u8],
+    ) -> Result<(), TwiError> {
+        if row >= ROWS || column >= COLUMNS {
+            return Ok(());
+        }
+
+        self.command(
+            bus,
+            Self::SET_DDRAM_ADDRESS | (Self::ROW_ADDRESSES[row] + column as u8),
+        )?;
+
+        // The address advances with each character
+        for character in text.iter().take(COLUMNS - column) {
+            self.write_byte(bus, *character, Self::RS)?;
+        }
+
+        Ok(())
+    }
+
+    fn set_power(&mut self, bus: &mut Twi, on: bool) -> Result<(), TwiError> {
+        self.backlight = on;
+
+        let command = if on {
+            Self::DISPLAY_ON
+        } else {
+            Self::DISPLAY_OFF
+        };
+
+        self.command(bus, command)
+    }
+}
@@ -0,0 +1,27 @@
+mod font;
+pub mod hd44780;
+pub mod ssd1306;
+
+use super::twi::{Twi, TwiError};
+
+/// Text geometry common to the displays: a 20x4 LCD, 4 spaced rows on the OLED
+pub const ROWS: usize = 4;
+pub const COLUMNS: usize = 20;
+
+/// Character display on the I2C bus. No frame buffer: the text is sent as it is written.
+pub trait TextDisplay {
+    /// Configures the controller, blank screen
+    fn init(&mut self, bus: &mut Twi) -> Result<(), TwiError>;
+
+    /// ASCII text from (row, column), clipped at the end of the row
+    fn write_text(
+        &mut self,
+        bus: &mut Twi,
+        row: usize,
+        column: usize,
+        text: &[u8],
+    ) -> Result<(), TwiError>;
+
+    /// Off: blank screen (no backlight), the content is kept for the next on
+    fn set_power(&mut self, bus: &mut Twi, on: bool) -> Result<(), TwiError>;
+}
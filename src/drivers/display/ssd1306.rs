@@ -0,0 +1,132 @@
+use super::{
+    font::{glyph, GLYPH_WIDTH},
+    TextDisplay, COLUMNS, ROWS,
+};
+use crate::drivers::twi::{I2cDevice, Twi, TwiError};
+
+/// SSD1306 128x64 OLED (I2C), text only: 6 pixel wide characters (glyph and a blank
+/// column) rendered from the flash font straight to the display RAM, a text row on every
+/// other 8 pixel page.
+pub struct Ssd1306 {
+    device: I2cDevice,
+}
+
+impl Ssd1306 {
+    /// SA0 low (high: 0x3D)
+    pub const ADDRESS: u8 = 0x3C;
+
+    const WIDTH: usize = 128;
+    const PAGES: u8 = 8;
+    const CHARACTER_WIDTH: usize = GLYPH_WIDTH + 1;
+    // Text centered horizontally
+    const LEFT_MARGIN: usize = (Self::WIDTH - COLUMNS * Self::CHARACTER_WIDTH) / 2;
+
+    // Control byte: what follows is a command stream, a data stream
+    const COMMANDS: u8 = 0x00;
+    const DATA: u8 = 0x40;
+    // Bytes per transfer after the control byte
+    const CHUNK_LEN: usize = 16;
+
+    const DISPLAY_OFF: u8 = 0xAE;
+    const DISPLAY_ON: u8 = 0xAF;
+    #[rustfmt::skip]
+    const INIT_COMMANDS: [u8; 23] = [
+        Self::DISPLAY_OFF,
+        0xD5, 0x80, // Clock divide ratio, oscillator frequency
+        0xA8, 0x3F, // Multiplex ratio: 64 lines
+        0xD3, 0x00, // No display offset
+        0x40,       // Start line 0
+        0x8D, 0x14, // Charge pump on (no external VCC)
+        0x20, 0x02, // Page addressing mode
+        0xA1,       // Segment remap and COM scan direction: not mirrored
+        0xC8,
+        0xDA, 0x12, // COM pins: alternative configuration
+        0x81, 0x7F, // Contrast (lower: slower pixel wear)
+        0xD9, 0xF1, // Pre-charge period
+        0xDB, 0x40, // VCOMH deselect level
+        0xA4,       // Display follows the RAM content
+    ];
+
+    pub fn new(address: u8) -> Self {
+        Self {
+            device: I2cDevice::new(address),
+        }
+    }
+
+    fn send(&self, bus: &mut Twi, control: u8, bytes: &[u8]) -> Result<(), TwiError> {
+        let mut buffer = [0; 1 + Self::CHUNK_LEN];
+        buffer[0] = control;
+
+        for chunk in bytes.chunks(Self::CHUNK_LEN) {
+            buffer[1..=chunk.len()].copy_from_slice(chunk);
+            self.device.write(bus, &buffer[..=chunk.len()])?;
+        }
+
+        Ok(())
+    }
+
+    fn set_position(&self, bus: &mut Twi, page: u8, x: usize) -> Result<(), TwiError> {
+        let x = x as u8;
+
+        self.send(
+            bus,
+            Self::COMMANDS,
+            &[0xB0 | page, x & 0x0F, 0x10 | (x >> 4)],
+        )
+    }
+
+    fn clear(&self, bus: &mut Twi) -> Result<(), TwiError> {
+        for page in 0..Self::PAGES {
+            self.set_position(bus, page, 0)?;
+            for _ in 0..Self::WIDTH / Self::CHUNK_LEN {
+                self.send(bus, Self::DATA, &[0; Self::CHUNK_LEN])?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl TextDisplay for Ssd1306 {
+    fn init(&mut self, bus: &mut Twi) -> Result<(), TwiError> {
+        self.send(bus, Self::COMMANDS, &Self::INIT_COMMANDS)?;
+        self.clear(bus)?;
+
+        self.send(bus, Self::COMMANDS, &[Self::DISPLAY_ON])
+    }
+
+    fn write_text(
+        &mut self,
+        bus: &mut Twi,
+        row: usize,
+        column: usize,
+        text: &[u8],
+    ) -> Result<(), TwiError> {
+        if row >= ROWS || column >= COLUMNS {
+            return Ok(());
+        }
+
+        let x = Self::LEFT_MARGIN + column * Self::CHARACTER_WIDTH;
+        self.set_position(bus, (row * 2) as u8, x)?;
+
+        // The column address advances with each byte
+        for character in text.iter().take(COLUMNS - column) {
+            let mut columns = [0; Self::CHARACTER_WIDTH];
+            columns[..GLYPH_WIDTH].copy_from_slice(&glyph(*character));
+
+            self.send(bus, Self::DATA, &columns)?;
+        }
+
+        Ok(())
+    }
+
+    fn set_power(&mut self, bus: &mut Twi, on: bool) -> Result<(), TwiError> {
+        let command = if on {
+            Self::DISPLAY_ON
+        } else {
+            Self::DISPLAY_OFF
+        };
+
+        self.send(bus, Self::COMMANDS, &[command])
+    }
+}
@@ -1,2 +1,9 @@
+pub mod adc;
+pub mod bh1750;
+pub mod buzzer;
+pub mod dimmer;
+pub mod display;
 pub mod stepper;
+pub mod switch;
 pub mod time;
+pub mod twi;
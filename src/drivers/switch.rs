@@ -0,0 +1,56 @@
+use super::time::timer::Timer;
+use arduino_hal::port::{
+    mode::{Input, PullUp},
+    Pin, PinOps,
+};
+
+/// Switch wired between the pin and GND, read through the internal pull-up:
+/// closed contact => pin low => active.
+pub struct Switch<SwitchPin: PinOps> {
+    pin: Pin<Input<PullUp>, SwitchPin>,
+    debounce_timer: Timer,
+    raw_active: bool,
+    active: bool,
+}
+
+impl<SwitchPin: PinOps> Switch<SwitchPin> {
+    /// Starts inactive: a contact already closed (or bouncing) at boot only becomes active
+    /// once it has stayed closed for the debounce time
+    pub fn new(pin: Pin<Input<PullUp>, SwitchPin>, debounce_us: u64) -> Self {
+        Self {
+            pin,
+            debounce_timer: Timer::new(debounce_us),
+            raw_active: false,
+            active: false,
+        }
+    }
+
+    /// Returns true when the debounced state has changed
+    pub fn update(&mut self, t_us: u64) -> bool {
+        let raw_active = self.pin.is_low();
+
+        if raw_active != self.raw_active {
+            // Contact is still moving: restart the debounce window
+            self.raw_active = raw_active;
+            self.debounce_timer.start(t_us);
+        }
+
+        if let Ok(has_expired) = self.debounce_timer.has_expired(t_us) {
+            if has_expired {
+                self.debounce_timer.stop();
+
+                if self.active != self.raw_active {
+                    self.active = self.raw_active;
+
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+}
@@ -12,6 +12,9 @@ use arduino_hal::pac::TC0;
 /// ║      1024 ║          125 ║              8 ms ║
 /// ║      1024 ║          250 ║             16 ms ║
 /// ╚═══════════╩══════════════╩═══════════════════╝
+///
+/// No PWM output left on Timer/Counter0 (see FastPwmTimer)
+#[allow(dead_code)]
 pub struct CtcTimer<const SYS_CLK_MHZ: u32, const PRESCALER: u32, const OF_COUNT: u8> {
     timer_counter: TC0,
     over_flow_period_us: u32,
@@ -50,11 +50,19 @@ impl<const SYS_CLK_MHZ: u32, const PRESCALER: u32> ImplTimer
     }
     fn micros(&self) -> u64 {
         let presc_clk_period_count = avr_device::interrupt::free(|cs| {
-            let ovflow_count = OVER_FLOW_COUNTER.borrow(cs).get();
-            ovflow_count * 256 + (self.timer_counter.tcnt0.read().bits() as u64)
+            let mut ovflow_count = OVER_FLOW_COUNTER.borrow(cs).get();
+            let count = self.timer_counter.tcnt0.read().bits();
+
+            // Overflow not handled yet (interrupts disabled): the counter has wrapped
+            if self.timer_counter.tifr0.read().tov0().bit_is_set() && count < u8::MAX {
+                ovflow_count += 1;
+            }
+
+            ovflow_count * 256 + (count as u64)
         });
 
-        (self.presc_clk_period_us as u64) * presc_clk_period_count
+        // presc_clk_period_us is the overflow period (256 prescaled clock periods)
+        (self.presc_clk_period_us as u64) * presc_clk_period_count / 256
     }
 }
 
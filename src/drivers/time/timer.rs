@@ -50,6 +50,24 @@ impl Timer {
         }
     }
 
+    pub fn get_elapsed_us(&self, micros_us: u64) -> Result<u64, TimerError> {
+        match self.state {
+            TimerState::Started { t_start_us } => Ok(micros_us - t_start_us),
+            TimerState::Stopped | TimerState::Expired => Err(TimerError::NotStarted),
+        }
+    }
+
+    pub fn get_expiry_us(&self) -> Option<u64> {
+        match self.state {
+            TimerState::Started { t_start_us } => Some(t_start_us + self.timeout_us),
+            TimerState::Stopped | TimerState::Expired => None,
+        }
+    }
+
+    pub fn get_timeout_us(&self) -> u64 {
+        self.timeout_us
+    }
+
     pub fn has_started(&self) -> bool {
         matches!(self.state, TimerState::Started { t_start_us: _ })
     }
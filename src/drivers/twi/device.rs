@@ -0,0 +1,24 @@
+use super::{Twi, TwiError};
+
+/// Device on the shared bus. The bus is lent to the devices for each transaction (see
+/// TaskContext): the transactions never interleave, a failed one leaves the bus recovered
+/// for the next device.
+#[derive(Clone, Copy)]
+pub struct I2cDevice {
+    address: u8,
+}
+
+impl I2cDevice {
+    /// 7-bit address
+    pub const fn new(address: u8) -> Self {
+        Self { address }
+    }
+
+    pub fn write(&self, bus: &mut Twi, bytes: &[u8]) -> Result<(), TwiError> {
+        bus.write(self.address, bytes)
+    }
+
+    pub fn read(&self, bus: &mut Twi, buffer: &mut [u8]) -> Result<(), TwiError> {
+        bus.read(self.address, buffer)
+    }
+}
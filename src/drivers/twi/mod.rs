@@ -0,0 +1,303 @@
+mod device;
+
+use arduino_hal::{
+    hal::port::{PC4, PC5},
+    pac::{PORTC, TWI},
+    port::{
+        mode::{Input, PullUp},
+        Pin,
+    },
+};
+pub use device::I2cDevice;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TwiError {
+    /// No ACK to the address: no such device
+    AddressNack,
+    /// Byte refused by the device
+    DataNack,
+    /// Another master (or noise) took the bus
+    ArbitrationLost,
+    /// Illegal START/STOP condition
+    BusError,
+    /// The hardware did not complete a step: bus held low
+    Timeout,
+}
+
+/// TWI master (I2C on A4 SDA, A5 SCL), blocking but bounded: each bus step times out instead
+/// of hanging the main loop. A timeout or a bus error recovers the bus (see recover).
+/// The devices share it through I2cDevice.
+pub struct Twi {
+    twi: TWI,
+    // Owned for the bus recovery, driven through PORTC (the TWI takes them over once enabled)
+    _sda: Pin<Input<PullUp>, PC4>,
+    _scl: Pin<Input<PullUp>, PC5>,
+    twbr: u8,
+    error_count: u16,
+    recovery_count: u16,
+}
+
+impl Twi {
+    const CPU_HZ: u32 = 16_000_000;
+
+    // A byte takes 90us at 100kHz: generous for slow devices (clock stretching)
+    const STEP_TIMEOUT_US: u16 = 1_000; // 1ms
+
+    // Recovery clock: ~100kHz
+    const RECOVERY_HALF_PERIOD_US: u32 = 5;
+
+    const SDA_MASK: u8 = 1 << 4;
+    const SCL_MASK: u8 = 1 << 5;
+
+    // TWSR status codes (prescaler bits masked)
+    const START: u8 = 0x08;
+    const SLA_W_ACK: u8 = 0x18;
+    const SLA_W_NACK: u8 = 0x20;
+    const DATA_W_ACK: u8 = 0x28;
+    const DATA_W_NACK: u8 = 0x30;
+    const ARBITRATION_LOST: u8 = 0x38;
+    const SLA_R_ACK: u8 = 0x40;
+    const SLA_R_NACK: u8 = 0x48;
+    const DATA_R_ACK: u8 = 0x50;
+    const DATA_R_NACK: u8 = 0x58;
+
+    /// Internal pull-ups only: external 4.7k pull-ups are recommended above a few cm of wire
+    pub fn new(
+        twi: TWI,
+        sda: Pin<Input<PullUp>, PC4>,
+        scl: Pin<Input<PullUp>, PC5>,
+        speed_hz: u32,
+    ) -> Self {
+        // SCL = CPU / (16 + 2 x TWBR x prescaler), prescaler 1
+        let twbr = ((Self::CPU_HZ / speed_hz).saturating_sub(16) / 2).min(u8::MAX as u32) as u8;
+
+        let mut twi = Self {
+            twi,
+            _sda: sda,
+            _scl: scl,
+            twbr,
+            error_count: 0,
+            recovery_count: 0,
+        };
+
+        // A device may have been left in the middle of a read by a reset
+        if !twi.is_sda_high() {
+            twi.recover();
+        } else {
+            twi.enable();
+        }
+
+        twi
+    }
+
+    pub fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), TwiError> {
+        let result = self
+            .start(address, false)
+            .and_then(|_| self.write_bytes(bytes));
+
+        self.finish(result)
+    }
+
+    pub fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), TwiError> {
+        let result = self
+            .start(address, true)
+            .and_then(|_| self.read_bytes(buffer));
+
+        self.finish(result)
+    }
+
+    /// A device answers at the address (empty write)
+    pub fn probe(&mut self, address: u8) -> bool {
+        let result = self.start(address, false);
+        let is_present = result.is_ok();
+
+        // No device is the expected answer of most addresses: not counted
+        match result {
+            Ok(_) | Err(TwiError::AddressNack) => {
+                let _ = self.stop();
+            }
+            Err(_) => {
+                let _ = self.finish(result);
+            }
+        }
+
+        is_present
+    }
+
+    /// Failed transactions (address NACK included)
+    pub fn error_count(&self) -> u16 {
+        self.error_count
+    }
+
+    pub fn recovery_count(&self) -> u16 {
+        self.recovery_count
+    }
+
+    fn enable(&mut self) {
+        // TWBR (Bit Rate Register), TWSR (Status Register): TWPS prescaler 1 (reset value)
+        self.twi.twbr.write(|w| w.bits(self.twbr));
+        self.twi.twsr.reset();
+        // TWCR (Control Register): TWEN, the TWI takes over the pins
+        self.twi.twcr.write(|w| w.twen().set_bit());
+    }
+
+    /// A device stuck in a read holds SDA low until it has shifted out its byte: up to 9 SCL
+    /// pulses, then a STOP. The pins are driven as open drain (low or pulled up).
+    fn recover(&mut self) {
+        self.recovery_count = self.recovery_count.saturating_add(1);
+
+        // TWI off: the pins are back to PORTC (inputs with pull-ups)
+        self.twi.twcr.reset();
+
+        for _ in 0..9 {
+            if self.is_sda_high() {
+                break;
+            }
+            Self::drive_low(Self::SCL_MASK);
+            arduino_hal::delay_us(Self::RECOVERY_HALF_PERIOD_US);
+            Self::release(Self::SCL_MASK);
+            arduino_hal::delay_us(Self::RECOVERY_HALF_PERIOD_US);
+        }
+
+        // STOP: SDA rising while SCL is high
+        Self::drive_low(Self::SCL_MASK);
+        arduino_hal::delay_us(Self::RECOVERY_HALF_PERIOD_US);
+        Self::drive_low(Self::SDA_MASK);
+        arduino_hal::delay_us(Self::RECOVERY_HALF_PERIOD_US);
+        Self::release(Self::SCL_MASK);
+        arduino_hal::delay_us(Self::RECOVERY_HALF_PERIOD_US);
+        Self::release(Self::SDA_MASK);
+        arduino_hal::delay_us(Self::RECOVERY_HALF_PERIOD_US);
+
+        self.enable();
+    }
+
+    fn is_sda_high(&self) -> bool {
+        let port = unsafe { &*PORTC::ptr() };
+
+        port.pinc.read().bits() & Self::SDA_MASK != 0
+    }
+
+    // Open drain low: output low (PORT bit cleared first, no high glitch)
+    fn drive_low(mask: u8) {
+        let port = unsafe { &*PORTC::ptr() };
+
+        port.portc
+            .modify(|r, w| unsafe { w.bits(r.bits() & !mask) });
+        port.ddrc.modify(|r, w| unsafe { w.bits(r.bits() | mask) });
+    }
+
+    // Open drain high: input with pull-up
+    fn release(mask: u8) {
+        let port = unsafe { &*PORTC::ptr() };
+
+        port.ddrc.modify(|r, w| unsafe { w.bits(r.bits() & !mask) });
+        port.portc.modify(|r, w| unsafe { w.bits(r.bits() | mask) });
+    }
+
+    /// STOP after the transaction, the bus recovered after a hardware failure
+    fn finish(&mut self, result: Result<(), TwiError>) -> Result<(), TwiError> {
+        let result = result.and_then(|_| self.stop());
+
+        match result {
+            Ok(_) => {}
+            Err(TwiError::Timeout) | Err(TwiError::BusError) => {
+                self.error_count = self.error_count.saturating_add(1);
+                self.recover();
+            }
+            Err(_) => {
+                self.error_count = self.error_count.saturating_add(1);
+                let _ = self.stop();
+            }
+        }
+
+        result
+    }
+
+    fn start(&mut self, address: u8, read: bool) -> Result<(), TwiError> {
+        // TWINT cleared (written 1): the hardware runs the step
+        self.twi
+            .twcr
+            .write(|w| w.twint().set_bit().twsta().set_bit().twen().set_bit());
+        match self.wait()? {
+            Self::START => {}
+            Self::ARBITRATION_LOST => return Err(TwiError::ArbitrationLost),
+            _ => return Err(TwiError::BusError),
+        }
+
+        self.twi.twdr.write(|w| w.bits(address << 1 | read as u8));
+        self.twi
+            .twcr
+            .write(|w| w.twint().set_bit().twen().set_bit());
+        match self.wait()? {
+            Self::SLA_W_ACK | Self::SLA_R_ACK => Ok(()),
+            Self::SLA_W_NACK | Self::SLA_R_NACK => Err(TwiError::AddressNack),
+            Self::ARBITRATION_LOST => Err(TwiError::ArbitrationLost),
+            _ => Err(TwiError::BusError),
+        }
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), TwiError> {
+        for byte in bytes {
+            self.twi.twdr.write(|w| w.bits(*byte));
+            self.twi
+                .twcr
+                .write(|w| w.twint().set_bit().twen().set_bit());
+            match self.wait()? {
+                Self::DATA_W_ACK => {}
+                Self::DATA_W_NACK => return Err(TwiError::DataNack),
+                Self::ARBITRATION_LOST => return Err(TwiError::ArbitrationLost),
+                _ => return Err(TwiError::BusError),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Every byte acknowledged but the last one: the device releases the bus
+    fn read_bytes(&mut self, buffer: &mut [u8]) -> Result<(), TwiError> {
+        let last = buffer.len().saturating_sub(1);
+
+        for (index, byte) in buffer.iter_mut().enumerate() {
+            let ack = index < last;
+            self.twi
+                .twcr
+                .write(|w| w.twint().set_bit().twea().bit(ack).twen().set_bit());
+            match self.wait()? {
+                Self::DATA_R_ACK | Self::DATA_R_NACK => *byte = self.twi.twdr.read().bits(),
+                Self::ARBITRATION_LOST => return Err(TwiError::ArbitrationLost),
+                _ => return Err(TwiError::BusError),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<(), TwiError> {
+        self.twi
+            .twcr
+            .write(|w| w.twint().set_bit().twsto().set_bit().twen().set_bit());
+
+        // TWSTO is cleared once the STOP is on the bus (TWINT is not set)
+        for _ in 0..Self::STEP_TIMEOUT_US {
+            if self.twi.twcr.read().twsto().bit_is_clear() {
+                return Ok(());
+            }
+            arduino_hal::delay_us(1);
+        }
+
+        Err(TwiError::Timeout)
+    }
+
+    /// Status once the step is done (TWINT set), at least STEP_TIMEOUT_US
+    fn wait(&self) -> Result<u8, TwiError> {
+        for _ in 0..Self::STEP_TIMEOUT_US {
+            if self.twi.twcr.read().twint().bit_is_set() {
+                return Ok(self.twi.twsr.read().bits() & 0xF8);
+            }
+            arduino_hal::delay_us(1);
+        }
+
+        Err(TwiError::Timeout)
+    }
+}
@@ -1,12 +1,14 @@
 #![no_std]
 #![no_main]
 #![feature(abi_avr_interrupt)]
+#![feature(asm_experimental_arch)]
+#![feature(asm_sym)]
+#![feature(naked_functions)]
 
 mod app;
 mod drivers;
 
 use app::Application;
-use panic_halt as _;
 
 #[arduino_hal::entry]
 fn main() -> ! {